@@ -0,0 +1,34 @@
+// Embeds `migrations/*.sql` into the binary, so a server can migrate its database
+// without the sources or sqlx-cli around.
+
+use std::{env, fs, io, path::Path};
+
+fn main() -> io::Result<()> {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("migrations");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut files = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.ends_with(".sql") => files.push((name.to_string(), path.clone())),
+            Some(_) | None => continue,
+        }
+    }
+    files.sort();
+
+    let mut out = String::from("&[\n");
+    for (name, path) in files {
+        out.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            name,
+            path.display().to_string()
+        ));
+    }
+    out.push(']');
+
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("migrations.rs"),
+        out,
+    )
+}
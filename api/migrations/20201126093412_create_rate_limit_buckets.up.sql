@@ -0,0 +1,7 @@
+-- Shared state of the rate limiter when API_RATE_LIMIT_STORE=postgres.
+-- It's disposable, so there's no point in writing it to WAL.
+CREATE UNLOGGED TABLE rate_limit_buckets (
+  key TEXT PRIMARY KEY,
+  tokens DOUBLE PRECISION NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
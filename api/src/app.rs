@@ -0,0 +1,125 @@
+use std::{io, path::Path, process};
+
+use clap::clap_app;
+
+use crate::{env, gql, server, tasks, telemetry};
+
+pub struct App(clap::App<'static>);
+
+impl App {
+    pub fn new() -> App {
+        App(clap_app!(api =>
+            (version: "0.0.1")
+            (about: "Rust + ReScript Demo API server. Serves the app when run without a subcommand.")
+            (author: "Alex Fedoseev <alex.fedoseev@gmail.com>")
+            (@arg migrate: --migrate "Applies pending migrations before serving the app")
+            (@subcommand schema =>
+                (about: "Prints GraphQL schema")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand sdl => (about: "Prints schema in SDL"))
+                (@subcommand json => (about: "Prints introspection result in JSON"))
+            )
+            (@subcommand posts =>
+                (about: "Post commands")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand export =>
+                    (about: "Exports posts")
+                    (@arg format: -f --format +takes_value +required "Either jsonl or markdown")
+                    (@arg out: -o --out +takes_value "File for jsonl (stdout by default) or directory for markdown")
+                )
+                (@subcommand import =>
+                    (about: "Imports posts: creates new ones and updates existing ones by id")
+                    (@arg format: -f --format +takes_value +required "Either jsonl or markdown")
+                    (@arg "dry-run": --"dry-run" "Prints what would change without writing anything")
+                    (@arg path: +required "File for jsonl or directory for markdown")
+                )
+            )
+            (@subcommand migrations =>
+                (about: "Database migration commands")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand run => (about: "Applies pending migrations"))
+                (@subcommand revert =>
+                    (about: "Reverts the latest migration")
+                    (@arg to: --to +takes_value "Reverts every migration applied after this version, 0 reverts all")
+                )
+                (@subcommand status => (about: "Prints applied, pending and modified migrations"))
+            )
+            (@subcommand seed =>
+                (about: "Inserts deterministic fake data")
+                (@arg count: -c --count +takes_value "Number of posts [default: 20]")
+                (@arg seed: -s --seed +takes_value "Seed of random generator [default: 42]")
+            )
+        ))
+    }
+
+    pub async fn run(self) -> io::Result<()> {
+        let app = self.0;
+        let matches = app.get_matches();
+
+        match matches.subcommand() {
+            Some(("schema", schema)) => match schema.subcommand() {
+                Some(("sdl", _)) => {
+                    println!("{}", gql::schema::sdl());
+                    Ok(())
+                }
+                Some(("json", _)) => {
+                    let introspection = gql::schema::introspection()
+                        .await
+                        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+                    println!("{}", serde_json::to_string_pretty(&introspection)?);
+                    Ok(())
+                }
+                Some(_) | None => unreachable!(), // guarded by `ArgRequiredElseHelp`
+            },
+            Some(("posts", posts)) => match posts.subcommand() {
+                Some(("export", args)) => {
+                    let format = tasks::posts::Format::parse(args.value_of("format").unwrap())?;
+                    tasks::posts::export(format, args.value_of("out").map(Path::new)).await
+                }
+                Some(("import", args)) => {
+                    let format = tasks::posts::Format::parse(args.value_of("format").unwrap())?;
+                    let path = Path::new(args.value_of("path").unwrap());
+                    tasks::posts::import(format, path, args.is_present("dry-run")).await
+                }
+                Some(_) | None => unreachable!(), // guarded by `ArgRequiredElseHelp`
+            },
+            Some(("migrations", migrations)) => match migrations.subcommand() {
+                Some(("run", _)) => tasks::migrations::run().await,
+                Some(("revert", args)) => {
+                    let to = match args.value_of("to") {
+                        Some(_) => Some(App::number(args, "to", 0)? as i64),
+                        None => None,
+                    };
+                    tasks::migrations::revert(to).await
+                }
+                Some(("status", _)) => tasks::migrations::status().await,
+                Some(_) | None => unreachable!(), // guarded by `ArgRequiredElseHelp`
+            },
+            Some(("seed", args)) => {
+                let count = App::number(args, "count", 20)?;
+                let seed = App::number(args, "seed", 42)?;
+                tasks::seed::exec(count as usize, seed).await
+            }
+            Some(_) => unreachable!(),
+            None => {
+                let telemetry = telemetry::init();
+                let migrate = matches.is_present("migrate") || env::api_migrate();
+                let outcome = server::run(migrate).await?;
+                drop(telemetry); // flushing spans, since `process::exit` doesn't run destructors
+                process::exit(outcome.exit_code())
+            }
+        }
+    }
+
+    fn number(args: &clap::ArgMatches, name: &str, default: u64) -> io::Result<u64> {
+        match args.value_of(name) {
+            Some(value) => value.parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} must be a non-negative number. Got: {}", name, value),
+                )
+            }),
+            None => Ok(default),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::env;
+use std::{env, net::IpAddr, time::Duration};
 
 pub fn web_host() -> String {
     env::var("WEB_HOST").expect("WEB_HOST is not set")
@@ -16,14 +16,188 @@ pub fn api_port() -> String {
     env::var("API_PORT").expect("API_PORT is not set")
 }
 
+pub fn api_tls_cert() -> Option<String> {
+    env::var("API_TLS_CERT")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+pub fn api_tls_key() -> Option<String> {
+    env::var("API_TLS_KEY").ok().filter(|path| !path.is_empty())
+}
+
+pub fn api_tls_port() -> u16 {
+    env::var("API_TLS_PORT")
+        .expect("API_TLS_PORT is not set")
+        .parse()
+        .expect("API_TLS_PORT must be a port number")
+}
+
+pub fn api_tls_redirect() -> bool {
+    env::var("API_TLS_REDIRECT")
+        .expect("API_TLS_REDIRECT is not set")
+        .parse()
+        .expect("API_TLS_REDIRECT must be either true or false")
+}
+
 pub fn api_graphql_path() -> String {
     env::var("API_GRAPHQL_PATH").expect("API_GRAPHQL_PATH is not set")
 }
 
+pub fn api_graphql_batch_limit() -> usize {
+    env::var("API_GRAPHQL_BATCH_LIMIT")
+        .expect("API_GRAPHQL_BATCH_LIMIT is not set")
+        .parse()
+        .expect("API_GRAPHQL_BATCH_LIMIT must be a number")
+}
+
+pub fn api_graphql_subscription_path() -> Option<String> {
+    env::var("API_GRAPHQL_SUBSCRIPTION_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+pub fn api_explorer() -> bool {
+    env::var("API_EXPLORER")
+        .expect("API_EXPLORER is not set")
+        .parse()
+        .expect("API_EXPLORER must be either true or false")
+}
+
+pub fn api_explorer_path() -> String {
+    env::var("API_EXPLORER_PATH").expect("API_EXPLORER_PATH is not set")
+}
+
+pub fn api_introspection() -> String {
+    env::var("API_INTROSPECTION").expect("API_INTROSPECTION is not set")
+}
+
+pub fn api_introspection_token() -> Option<String> {
+    env::var("API_INTROSPECTION_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+pub fn api_explorer_examples() -> Option<String> {
+    env::var("API_EXPLORER_EXAMPLES")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+pub fn api_migrate() -> bool {
+    env::var("API_MIGRATE")
+        .expect("API_MIGRATE is not set")
+        .parse()
+        .expect("API_MIGRATE must be either true or false")
+}
+
 pub fn api_health_path() -> String {
     env::var("API_HEALTH_PATH").expect("API_HEALTH_PATH is not set")
 }
 
+pub fn api_readiness_path() -> String {
+    env::var("API_READINESS_PATH").expect("API_READINESS_PATH is not set")
+}
+
+pub fn api_shutdown_grace() -> Duration {
+    let secs = env::var("API_SHUTDOWN_GRACE").expect("API_SHUTDOWN_GRACE is not set");
+    Duration::from_secs(
+        secs.parse()
+            .expect("API_SHUTDOWN_GRACE must be a number of seconds"),
+    )
+}
+
+pub fn api_shutdown_timeout() -> Duration {
+    let secs = env::var("API_SHUTDOWN_TIMEOUT").expect("API_SHUTDOWN_TIMEOUT is not set");
+    Duration::from_secs(
+        secs.parse()
+            .expect("API_SHUTDOWN_TIMEOUT must be a number of seconds"),
+    )
+}
+
+pub fn api_cors_origins() -> Option<Vec<String>> {
+    let origins = env::var("API_CORS_ORIGINS").expect("API_CORS_ORIGINS is not set");
+    if origins.trim().is_empty() {
+        None
+    } else {
+        Some(list(&origins))
+    }
+}
+
+pub fn api_cors_scheme() -> String {
+    env::var("API_CORS_SCHEME").expect("API_CORS_SCHEME is not set")
+}
+
+pub fn api_cors_allowed_headers() -> Vec<String> {
+    list(&env::var("API_CORS_ALLOWED_HEADERS").expect("API_CORS_ALLOWED_HEADERS is not set"))
+}
+
+pub fn api_cors_credentials() -> bool {
+    env::var("API_CORS_CREDENTIALS")
+        .expect("API_CORS_CREDENTIALS is not set")
+        .parse()
+        .expect("API_CORS_CREDENTIALS must be either true or false")
+}
+
+pub fn api_cors_max_age() -> usize {
+    env::var("API_CORS_MAX_AGE")
+        .expect("API_CORS_MAX_AGE is not set")
+        .parse()
+        .expect("API_CORS_MAX_AGE must be a number of seconds")
+}
+
+pub fn api_rate_limit_store() -> String {
+    env::var("API_RATE_LIMIT_STORE").expect("API_RATE_LIMIT_STORE is not set")
+}
+
+pub fn api_rate_limit_query_burst() -> f64 {
+    env::var("API_RATE_LIMIT_QUERY_BURST")
+        .expect("API_RATE_LIMIT_QUERY_BURST is not set")
+        .parse()
+        .expect("API_RATE_LIMIT_QUERY_BURST must be a number")
+}
+
+pub fn api_rate_limit_query_rate() -> f64 {
+    env::var("API_RATE_LIMIT_QUERY_RATE")
+        .expect("API_RATE_LIMIT_QUERY_RATE is not set")
+        .parse()
+        .expect("API_RATE_LIMIT_QUERY_RATE must be a number")
+}
+
+pub fn api_rate_limit_mutation_burst() -> f64 {
+    env::var("API_RATE_LIMIT_MUTATION_BURST")
+        .expect("API_RATE_LIMIT_MUTATION_BURST is not set")
+        .parse()
+        .expect("API_RATE_LIMIT_MUTATION_BURST must be a number")
+}
+
+pub fn api_rate_limit_mutation_rate() -> f64 {
+    env::var("API_RATE_LIMIT_MUTATION_RATE")
+        .expect("API_RATE_LIMIT_MUTATION_RATE is not set")
+        .parse()
+        .expect("API_RATE_LIMIT_MUTATION_RATE must be a number")
+}
+
+pub fn api_trusted_proxies() -> Vec<IpAddr> {
+    list(&env::var("API_TRUSTED_PROXIES").expect("API_TRUSTED_PROXIES is not set"))
+        .iter()
+        .map(|ip| {
+            ip.parse()
+                .expect("API_TRUSTED_PROXIES must be a comma separated list of IP addresses")
+        })
+        .collect()
+}
+
+pub fn api_metrics_path() -> String {
+    env::var("API_METRICS_PATH").expect("API_METRICS_PATH is not set")
+}
+
+pub fn api_otlp_endpoint() -> Option<String> {
+    env::var("API_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+}
+
 pub fn pg_host() -> String {
     env::var("PG_HOST").expect("PG_HOST is not set")
 }
@@ -43,3 +217,12 @@ pub fn pg_password() -> String {
 pub fn pg_database() -> String {
     env::var("PG_DATABASE").expect("PG_DATABASE is not set")
 }
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
@@ -1,26 +1,338 @@
 pub mod api {
-    use actix_web::web::Data;
-    use graphql_actix_web::{Request as GqlRequest, Response as GqlResponse};
+    use std::time::{Duration, Instant};
 
-    use crate::{gql::schema::GqlSchema, pg::PgPool};
+    use actix_web::{
+        http::{header, Method},
+        web::Data,
+        Either, HttpRequest, HttpResponse,
+    };
+    use futures::future;
+    use graphql::parser::{
+        parse_query,
+        types::{DocumentOperations, OperationType},
+    };
+    use graphql::BatchRequest;
+    use graphql_actix_web::{BatchRequest as GqlBatchRequest, Response as GqlResponse};
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        env,
+        gql::{
+            result,
+            schema::{GqlSchema, RestrictedSchema},
+            GqlError,
+        },
+        metrics,
+        pg::PgPool,
+        rate_limit::{Budget, Decision, Limiter},
+    };
+
+    gql_error!(
+        pub enum Error {
+            // Payload is a number of seconds to wait before retrying
+            RateLimited(u64),
+            // Payload is the max number of operations allowed in a batch
+            BatchTooLarge(usize),
+        }
+    );
+
+    // Who can introspect the schema through the endpoint. Other requests are executed
+    // against a copy of the schema that rejects introspection.
+    pub enum Introspection {
+        Public,
+        // Requires `Authorization: Bearer <API_INTROSPECTION_TOKEN>`
+        Token(String),
+        Disabled,
+    }
+
+    impl Introspection {
+        pub fn from_env() -> Self {
+            match env::api_introspection().as_str() {
+                "public" => Introspection::Public,
+                "token" => match env::api_introspection_token() {
+                    Some(token) => Introspection::Token(token),
+                    None => panic!(
+                        "API_INTROSPECTION_TOKEN must be set when API_INTROSPECTION is token"
+                    ),
+                },
+                "disabled" => Introspection::Disabled,
+                value => panic!(
+                    "API_INTROSPECTION must be one of public, token or disabled. Got: {}",
+                    value
+                ),
+            }
+        }
+
+        pub fn is_enabled(&self) -> bool {
+            !matches!(self, Introspection::Disabled)
+        }
+
+        // Token is accepted only in a header, since query strings end up in access logs
+        fn is_allowed(&self, req: &HttpRequest) -> bool {
+            match self {
+                Introspection::Public => true,
+                Introspection::Disabled => false,
+                Introspection::Token(expected) => req
+                    .headers()
+                    .get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .map(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+                    .unwrap_or(false),
+            }
+        }
+    }
+
+    // Max number of operations in a batched request
+    pub struct BatchLimit(pub usize);
+
+    impl BatchLimit {
+        pub fn from_env() -> Self {
+            Self(env::api_graphql_batch_limit())
+        }
+    }
 
     pub async fn endpoint(
         pg: Data<PgPool>,
         schema: Data<GqlSchema>,
-        req: GqlRequest,
-    ) -> GqlResponse {
-        schema.execute(req.into_inner().data(pg)).await.into()
+        restricted: Data<RestrictedSchema>,
+        introspection: Data<Introspection>,
+        limiter: Data<Option<Limiter>>,
+        batch_limit: Data<BatchLimit>,
+        http_req: HttpRequest,
+        req: GqlBatchRequest,
+    ) -> Either<GqlResponse, HttpResponse> {
+        let (reqs, is_batch) = match req.into_inner() {
+            BatchRequest::Single(req) => (vec![req], false),
+            BatchRequest::Batch(reqs) => (reqs, true),
+        };
+        let is_get = http_req.method() == Method::GET;
+
+        // Every operation in a batch costs about the same as a separate request,
+        // so an unbounded batch would let a single request do an arbitrary amount of work
+        let batch_limit = batch_limit.0;
+        if reqs.len() > batch_limit {
+            return Either::B(HttpResponse::BadRequest().json(result::response_body(
+                GqlError::Extended(Error::BatchTooLarge(batch_limit)),
+            )));
+        }
+
+        let operations: Vec<(String, bool)> = reqs
+            .iter()
+            .map(|req| {
+                let name = req
+                    .operation_name
+                    .clone()
+                    .unwrap_or_else(|| metrics::gql::ANONYMOUS_OPERATION.to_string());
+                (name, is_mutation(req))
+            })
+            .collect();
+
+        // GET requests can be cached by browsers and CDNs, so those must be side-effect free
+        if is_get && operations.iter().any(|(_, is_mutation)| *is_mutation) {
+            return Either::B(
+                HttpResponse::MethodNotAllowed()
+                    .set_header(header::ALLOW, "POST")
+                    .finish(),
+            );
+        }
+
+        // Each operation of a batch is charged separately. The whole batch is rejected on the first
+        // operation that is over the limit, so clients don't have to deal with partially executed batches
+        // and operations after it aren't charged for nothing.
+        if let Some(limiter) = limiter.as_ref() {
+            let subject = limiter.subject(&http_req);
+            for (operation, is_mutation) in &operations {
+                let budget = if *is_mutation {
+                    Budget::Mutation
+                } else {
+                    Budget::Query
+                };
+                if let Decision::Limited { retry_after } = limiter.take(&subject, budget).await {
+                    metrics::gql::observe(operation, "RATE_LIMITED", Duration::default());
+                    return Either::B(rate_limited(retry_after));
+                }
+            }
+        }
+
+        let schema: &GqlSchema = if introspection.is_allowed(&http_req) {
+            &schema
+        } else {
+            &restricted.0
+        };
+        let mut responses = future::join_all(
+            reqs.into_iter()
+                .zip(&operations)
+                .map(|(req, (operation, _))| execute(schema, &pg, req, operation)),
+        )
+        .await;
+
+        if is_batch {
+            Either::A(graphql::BatchResponse::Batch(responses).into())
+        } else {
+            let res = responses.remove(0);
+            if is_get {
+                Either::B(cacheable(&http_req, res))
+            } else {
+                Either::A(res.into())
+            }
+        }
+    }
+
+    async fn execute(
+        schema: &GqlSchema,
+        pg: &Data<PgPool>,
+        req: graphql::Request,
+        operation: &str,
+    ) -> graphql::Response {
+        let start = Instant::now();
+        let res = schema.execute(req.data(pg.clone())).await;
+        let elapsed = start.elapsed();
+
+        match res.errors.first() {
+            None => metrics::gql::observe(operation, metrics::gql::NO_ERROR, elapsed),
+            Some(error) => metrics::gql::observe(operation, &result::reason(error), elapsed),
+        }
+
+        res
+    }
+
+    // If the operation can't be parsed, it's treated as a query,
+    // since the executor would reject it anyway
+    fn is_mutation(req: &graphql::Request) -> bool {
+        let doc = match parse_query(&req.query) {
+            Ok(doc) => doc,
+            Err(_) => return false,
+        };
+        match doc.operations {
+            DocumentOperations::Single(op) => op.node.ty == OperationType::Mutation,
+            DocumentOperations::Multiple(ops) => match &req.operation_name {
+                Some(name) => ops.iter().any(|(op_name, op)| {
+                    op_name.as_str() == name && op.node.ty == OperationType::Mutation
+                }),
+                None => ops.values().any(|op| op.node.ty == OperationType::Mutation),
+            },
+        }
+    }
+
+    // Responses to GET requests carry a weak ETag (compression changes the bytes on the wire)
+    // and a Cache-Control header derived from the cache hints of the requested fields
+    fn cacheable(http_req: &HttpRequest, res: graphql::Response) -> HttpResponse {
+        if !res.is_ok() {
+            return HttpResponse::Ok().json(res);
+        }
+
+        let body = match serde_json::to_vec(&res) {
+            Ok(body) => body,
+            Err(error) => {
+                error!(format!("Failed to serialize GraphQL response: {}", error));
+                return HttpResponse::InternalServerError()
+                    .json(result::response_body::<()>(GqlError::InternalServerError));
+            }
+        };
+        // Hash must be stable across builds, so instances behind a CDN agree on ETags
+        let etag = format!("W/\"{:x}\"", Sha256::digest(&body));
+        let cache_control = res
+            .cache_control
+            .value()
+            .unwrap_or_else(|| "no-cache".to_string());
+
+        let not_modified = http_req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim() == "*" || weak_eq(tag.trim(), &etag))
+            })
+            .unwrap_or(false);
+
+        if not_modified {
+            HttpResponse::NotModified()
+                .set_header(header::ETAG, etag)
+                .set_header(header::CACHE_CONTROL, cache_control)
+                .finish()
+        } else {
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .set_header(header::ETAG, etag)
+                .set_header(header::CACHE_CONTROL, cache_control)
+                .body(body)
+        }
+    }
+
+    // If-None-Match uses weak comparison: `W/"x"` and `"x"` are the same tag
+    fn weak_eq(a: &str, b: &str) -> bool {
+        a.strip_prefix("W/").unwrap_or(a) == b.strip_prefix("W/").unwrap_or(b)
+    }
+
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+    fn rate_limited(retry_after: Duration) -> HttpResponse {
+        // Rounding up, so a client that respects the header doesn't hit the limit again
+        let secs = retry_after.as_secs() + if retry_after.subsec_nanos() > 0 { 1 } else { 0 };
+        HttpResponse::TooManyRequests()
+            .set_header(header::RETRY_AFTER, secs.to_string())
+            .json(result::response_body(GqlError::Extended(
+                Error::RateLimited(secs),
+            )))
     }
 }
 
-#[cfg(debug_assertions)]
-pub mod playground {
-    use actix_web::{HttpResponse, Result};
-    use graphql::http::{playground_source, GraphQLPlaygroundConfig};
+pub mod explorer {
+    use std::fs;
+
+    use actix_web::{web::Data, HttpResponse};
+
+    use super::api::Introspection;
+    use crate::env;
+
+    const TEMPLATE: &str = include_str!("http/explorer.html");
+    const EXAMPLES: &str = include_str!("http/examples.graphql");
+
+    // Page itself holds nothing secret. What it shows is up to `Introspection` of the endpoint.
+    pub struct Explorer {
+        pub path: String,
+        page: String,
+    }
+
+    impl Explorer {
+        // Returns `None` when the explorer is switched off
+        pub fn from_env(introspection: &Introspection) -> Option<Self> {
+            if !env::api_explorer() {
+                return None;
+            }
+            if !introspection.is_enabled() {
+                panic!("API_EXPLORER requires API_INTROSPECTION to be either public or token");
+            }
+
+            let examples = match env::api_explorer_examples() {
+                Some(path) => fs::read_to_string(&path).unwrap_or_else(|error| {
+                    panic!("Failed to read API_EXPLORER_EXAMPLES {}: {}", path, error)
+                }),
+                None => EXAMPLES.to_string(),
+            };
+            let config = serde_json::json!({
+                "endpoint": env::api_graphql_path(),
+                "subscriptionEndpoint": env::api_graphql_subscription_path(),
+                "examples": examples,
+            });
+            // Config is inlined into a <script> tag, so it must not be able to close it
+            let config = config.to_string().replace("</", "<\\/");
+
+            Some(Self {
+                path: env::api_explorer_path(),
+                page: TEMPLATE.replace("__CONFIG__", &config),
+            })
+        }
+    }
 
-    pub async fn endpoint() -> Result<HttpResponse> {
-        Ok(HttpResponse::Ok()
+    pub async fn endpoint(explorer: Data<Explorer>) -> HttpResponse {
+        HttpResponse::Ok()
             .content_type("text/html; charset=utf-8")
-            .body(playground_source(GraphQLPlaygroundConfig::new("/api"))))
+            .body(explorer.page.clone())
     }
 }
@@ -1,13 +1,17 @@
-pub mod http;
-pub mod inputs;
-pub mod schema;
-
 #[macro_use]
 mod db;
 #[macro_use]
 mod result;
 
+pub mod http;
+pub mod inputs;
+pub mod outputs;
+pub mod schema;
+
 mod mutations;
 mod queries;
 
 use result::{GqlError, GqlOk, GqlResult};
+
+#[cfg(test)]
+pub use result::reason;
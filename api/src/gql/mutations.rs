@@ -1,3 +1,7 @@
+pub mod post_bulk;
 pub mod post_create;
+pub mod post_create_many;
 pub mod post_delete;
+pub mod post_delete_many;
 pub mod post_update;
+pub mod post_update_many;
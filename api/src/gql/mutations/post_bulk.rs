@@ -0,0 +1,102 @@
+use std::{collections::HashSet, hash::Hash};
+
+use graphql::Context as GqlContext;
+
+use crate::{
+    gql::mutations::{post_delete, post_update},
+    gql::{
+        outputs::{PostBulkItem, PostBulkResult},
+        GqlError, GqlResult,
+    },
+    pg::PgTx,
+};
+
+// Each item costs about as much as a separate mutation, so bulk operations are capped
+// the same way batches are, instead of letting a single operation do an arbitrary amount of work
+pub const MAX_ITEMS: usize = 100;
+
+gql_error!(
+    pub enum Error {
+        // Payload is the max number of items in a bulk operation
+        TooManyItems(usize),
+    }
+);
+
+// Errors of individual items are reported in the result rather than in `errors`,
+// so they're exposed as a GraphQL enum with the same reasons as the single post mutations
+gql_error!(
+    #[derive(graphql::Enum, PartialEq, Eq)]
+    #[graphql(name = "PostBulkErrorReason")]
+    pub enum ItemError {
+        PostNotFound,
+        // Reported for every occurrence of an id but the first one
+        DuplicateId,
+    }
+);
+
+impl From<post_update::Error> for ItemError {
+    fn from(error: post_update::Error) -> Self {
+        match error {
+            post_update::Error::PostNotFound => ItemError::PostNotFound,
+        }
+    }
+}
+
+impl From<post_delete::Error> for ItemError {
+    fn from(error: post_delete::Error) -> Self {
+        match error {
+            post_delete::Error::PostNotFound => ItemError::PostNotFound,
+        }
+    }
+}
+
+pub fn check_len(len: usize) -> GqlResult<(), Error> {
+    if len > MAX_ITEMS {
+        Err(GqlError::Extended(Error::TooManyItems(MAX_ITEMS)))
+    } else {
+        Ok(())
+    }
+}
+
+// Marks repeated occurrences of ids, so each post is changed at most once per operation
+pub fn duplicates<T: Eq + Hash>(ids: &[T]) -> Vec<bool> {
+    let mut seen = HashSet::with_capacity(ids.len());
+    ids.iter().map(|id| !seen.insert(id)).collect()
+}
+
+pub async fn begin(ctx: &GqlContext<'_>) -> GqlResult<PgTx, Error> {
+    match db!(ctx)?.begin().await {
+        Ok(tx) => Ok(tx),
+        Err(error) => {
+            error!(error);
+            Err(GqlError::InternalServerError)
+        }
+    }
+}
+
+// Non-atomic operations commit whatever succeeded. Atomic ones are rolled back if any item failed,
+// then successful items are reported without a post, since nothing was actually changed.
+pub async fn finish(
+    tx: PgTx,
+    mut items: Vec<PostBulkItem>,
+    atomic: bool,
+) -> GqlResult<PostBulkResult, Error> {
+    let committed = !(atomic && items.iter().any(PostBulkItem::is_error));
+
+    let res = if committed {
+        tx.commit().await
+    } else {
+        for item in items.iter_mut() {
+            item.post = None;
+        }
+        tx.rollback().await
+    };
+
+    match res {
+        Ok(()) => Ok(PostBulkResult { committed, items }),
+        Err(error) => {
+            error!(error);
+            Err(GqlError::InternalServerError)
+        }
+    }
+}
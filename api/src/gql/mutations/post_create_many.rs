@@ -0,0 +1,33 @@
+use graphql::Context as GqlContext;
+
+use crate::{
+    gql::{
+        inputs::CreatePostInput,
+        mutations::post_bulk,
+        outputs::{PostBulkItem, PostBulkResult},
+        GqlError, GqlResult,
+    },
+    pg::queries as db,
+};
+
+// Creation can't fail for an individual post, so `atomic` changes nothing for now.
+// It's accepted anyway, so all bulk mutations can be called the same way.
+pub async fn exec(
+    inputs: Vec<CreatePostInput>,
+    atomic: bool,
+    ctx: &GqlContext<'_>,
+) -> GqlResult<PostBulkResult, post_bulk::Error> {
+    post_bulk::check_len(inputs.len())?;
+    let mut tx = post_bulk::begin(ctx).await?;
+
+    match db::post_create_many::exec(inputs, &mut tx).await {
+        Ok(posts) => {
+            let items = posts.into_iter().map(PostBulkItem::ok).collect();
+            post_bulk::finish(tx, items, atomic).await
+        }
+        Err(error) => {
+            error!(error);
+            Err(GqlError::InternalServerError)
+        }
+    }
+}
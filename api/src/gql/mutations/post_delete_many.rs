@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use graphql::Context as GqlContext;
+
+use crate::{
+    gql::{
+        mutations::{post_bulk, post_delete},
+        outputs::{PostBulkItem, PostBulkResult},
+        GqlError, GqlResult,
+    },
+    models::post::PostId,
+    pg::queries as db,
+};
+
+pub async fn exec(
+    ids: Vec<PostId>,
+    atomic: bool,
+    ctx: &GqlContext<'_>,
+) -> GqlResult<PostBulkResult, post_bulk::Error> {
+    post_bulk::check_len(ids.len())?;
+    let duplicates = post_bulk::duplicates(&ids.iter().map(PostId::as_uuid).collect::<Vec<_>>());
+    let mut tx = post_bulk::begin(ctx).await?;
+
+    let deleted: HashMap<_, _> = match db::post_delete_many::exec(&ids, &mut tx).await {
+        Ok(posts) => posts
+            .into_iter()
+            .map(|post| (*post.id.as_uuid(), post))
+            .collect(),
+        Err(error) => {
+            error!(error);
+            return Err(GqlError::InternalServerError);
+        }
+    };
+    let items = ids
+        .iter()
+        .zip(duplicates)
+        .map(|(id, duplicate)| match deleted.get(id.as_uuid()) {
+            _ if duplicate => PostBulkItem::error(post_bulk::ItemError::DuplicateId),
+            Some(post) => PostBulkItem::ok(post.clone()),
+            None => PostBulkItem::error(post_delete::Error::PostNotFound),
+        })
+        .collect();
+
+    post_bulk::finish(tx, items, atomic).await
+}
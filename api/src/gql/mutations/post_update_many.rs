@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use graphql::Context as GqlContext;
+
+use crate::{
+    gql::{
+        inputs::UpdatePostInput,
+        mutations::{post_bulk, post_update},
+        outputs::{PostBulkItem, PostBulkResult},
+        GqlError, GqlResult,
+    },
+    pg::queries as db,
+};
+
+pub async fn exec(
+    inputs: Vec<UpdatePostInput>,
+    atomic: bool,
+    ctx: &GqlContext<'_>,
+) -> GqlResult<PostBulkResult, post_bulk::Error> {
+    post_bulk::check_len(inputs.len())?;
+    let ids: Vec<_> = inputs.iter().map(|input| *input.id.as_uuid()).collect();
+    let duplicates = post_bulk::duplicates(&ids);
+    let inputs = inputs
+        .into_iter()
+        .zip(&duplicates)
+        .filter(|(_, duplicate)| !**duplicate)
+        .map(|(input, _)| input)
+        .collect();
+    let mut tx = post_bulk::begin(ctx).await?;
+
+    let updated: HashMap<_, _> = match db::post_update_many::exec(inputs, &mut tx).await {
+        Ok(posts) => posts
+            .into_iter()
+            .map(|post| (*post.id.as_uuid(), post))
+            .collect(),
+        Err(error) => {
+            error!(error);
+            return Err(GqlError::InternalServerError);
+        }
+    };
+    let items = ids
+        .iter()
+        .zip(duplicates)
+        .map(|(id, duplicate)| match updated.get(id) {
+            _ if duplicate => PostBulkItem::error(post_bulk::ItemError::DuplicateId),
+            Some(post) => PostBulkItem::ok(post.clone()),
+            None => PostBulkItem::error(post_update::Error::PostNotFound),
+        })
+        .collect();
+
+    post_bulk::finish(tx, items, atomic).await
+}
@@ -0,0 +1,2 @@
+pub mod post_bulk;
+pub use self::post_bulk::{PostBulkItem, PostBulkResult};
@@ -0,0 +1,43 @@
+use crate::{gql::mutations::post_bulk::ItemError, models::post::Post};
+
+#[derive(graphql::SimpleObject)]
+pub struct PostBulkResult {
+    /// False when an atomic operation failed for some of the items and nothing was changed
+    pub committed: bool,
+    /// One item per input, in the same order
+    pub items: Vec<PostBulkItem>,
+}
+
+#[derive(graphql::SimpleObject)]
+pub struct PostBulkItem {
+    pub post: Option<Post>,
+    pub error: Option<PostBulkError>,
+}
+
+/// Same reason as `extensions.details` of the single post mutations. Item errors have no payload.
+#[derive(graphql::SimpleObject)]
+pub struct PostBulkError {
+    pub reason: ItemError,
+}
+
+impl PostBulkItem {
+    pub fn ok(post: Post) -> Self {
+        Self {
+            post: Some(post),
+            error: None,
+        }
+    }
+
+    pub fn error<E: Into<ItemError>>(error: E) -> Self {
+        Self {
+            post: None,
+            error: Some(PostBulkError {
+                reason: error.into(),
+            }),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.error.is_some()
+    }
+}
@@ -38,6 +38,38 @@ impl<E: Serialize> Into<graphql::Error> for GqlError<E> {
     }
 }
 
+// Body of a response that's failed before reaching the executor
+pub fn response_body<E: Serialize>(error: GqlError<E>) -> serde_json::Value {
+    match error {
+        GqlError::Extended(error) => serde_json::json!({
+            "errors": [{
+                "message": EXTENDED_ERROR,
+                "extensions": { "details": error },
+            }]
+        }),
+        GqlError::InternalServerError => serde_json::json!({
+            "errors": [{ "message": INTERNAL_SERVER_ERROR }]
+        }),
+    }
+}
+
+// Reason of the failure as it's reported to the client, e.g. `POST_NOT_FOUND`
+pub fn reason(error: &graphql::ServerError) -> String {
+    match error.message.as_str() {
+        EXTENDED_ERROR => serde_json::to_value(error)
+            .ok()
+            .and_then(|error| {
+                error
+                    .pointer("/extensions/details/reason")
+                    .and_then(|reason| reason.as_str())
+                    .map(ToOwned::to_owned)
+            })
+            .unwrap_or_else(|| "EXTENDED_ERROR".to_string()),
+        INTERNAL_SERVER_ERROR => "INTERNAL_SERVER_ERROR".to_string(),
+        _ => "INVALID_REQUEST".to_string(),
+    }
+}
+
 #[macro_export]
 macro_rules! gql_error {
     ($error:item) => {
@@ -1,9 +1,11 @@
-use graphql::{Context, EmptySubscription, FieldResult, Schema};
+use graphql::{extensions::Tracing, Context, EmptySubscription, FieldResult, Schema};
 
 use crate::{
     gql::{
         inputs::{CreatePostInput, UpdatePostInput},
-        mutations, queries, GqlError, GqlOk,
+        mutations,
+        outputs::PostBulkResult,
+        queries, GqlError, GqlOk,
     },
     models::post::{Post, PostId},
 };
@@ -11,7 +13,38 @@ use crate::{
 pub type GqlSchema = Schema<Query, Mutation, EmptySubscription>;
 
 pub fn new() -> GqlSchema {
-    Schema::build(Query, Mutation, EmptySubscription).finish()
+    Schema::build(Query, Mutation, EmptySubscription)
+        .extension(Tracing)
+        .finish()
+}
+
+// Served to requests that aren't allowed to introspect the schema
+#[derive(Clone)]
+pub struct RestrictedSchema(pub GqlSchema);
+
+pub fn restricted() -> RestrictedSchema {
+    RestrictedSchema(
+        Schema::build(Query, Mutation, EmptySubscription)
+            .extension(Tracing)
+            .disable_introspection()
+            .finish(),
+    )
+}
+
+// Same query `get-graphql-schema` sends, so the output matches what's committed to the client
+const INTROSPECTION_QUERY: &str = include_str!("introspection.graphql");
+
+pub fn sdl() -> String {
+    new().sdl()
+}
+
+// Neither SDL nor introspection touches resolvers, so no database is required
+pub async fn introspection() -> Result<serde_json::Value, String> {
+    let res = new().execute(INTROSPECTION_QUERY).await;
+    match res.errors.first() {
+        None => serde_json::to_value(&res.data).map_err(|error| error.to_string()),
+        Some(error) => Err(error.message.to_owned()),
+    }
 }
 
 pub struct Query;
@@ -19,11 +52,13 @@ pub struct Mutation;
 
 #[graphql::Object]
 impl Query {
+    #[graphql(cache_control(max_age = 10))]
     async fn posts(&self, ctx: &Context<'_>) -> FieldResult<Vec<Post>> {
         queries::post_get_all::exec(ctx)
             .await
             .map_err(GqlError::into)
     }
+    #[graphql(cache_control(max_age = 30))]
     async fn post(&self, ctx: &Context<'_>, id: PostId) -> FieldResult<Post> {
         queries::post_get_by_id::exec(id, ctx)
             .await
@@ -48,4 +83,204 @@ impl Mutation {
             .await
             .map_err(GqlError::into)
     }
+    async fn create_posts(
+        &self,
+        ctx: &Context<'_>,
+        inputs: Vec<CreatePostInput>,
+        #[graphql(default)] atomic: bool,
+    ) -> FieldResult<PostBulkResult> {
+        mutations::post_create_many::exec(inputs, atomic, ctx)
+            .await
+            .map_err(GqlError::into)
+    }
+    async fn update_posts(
+        &self,
+        ctx: &Context<'_>,
+        inputs: Vec<UpdatePostInput>,
+        #[graphql(default)] atomic: bool,
+    ) -> FieldResult<PostBulkResult> {
+        mutations::post_update_many::exec(inputs, atomic, ctx)
+            .await
+            .map_err(GqlError::into)
+    }
+    async fn delete_posts(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<PostId>,
+        #[graphql(default)] atomic: bool,
+    ) -> FieldResult<PostBulkResult> {
+        mutations::post_delete_many::exec(ids, atomic, ctx)
+            .await
+            .map_err(GqlError::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        gql::mutations::post_bulk,
+        testing::{self, TestApi},
+    };
+
+    const CREATE_POST: &str = "
+        mutation CreatePost($input: CreatePostInput!) {
+            createPost(input: $input) { id title content }
+        }
+    ";
+
+    async fn create_post(api: &TestApi, title: &str) -> serde_json::Value {
+        let res = api
+            .execute(
+                CREATE_POST,
+                json!({ "input": { "title": title, "content": "Content" } }),
+            )
+            .await;
+        testing::data(&res)["createPost"].clone()
+    }
+
+    #[actix_rt::test]
+    async fn creates_and_fetches_post() {
+        let api = TestApi::new().await;
+        let post = create_post(&api, "Title").await;
+
+        let res = api
+            .execute(
+                "query Post($id: PostId!) { post(id: $id) { id title content } }",
+                json!({ "id": post["id"] }),
+            )
+            .await;
+
+        assert_eq!(testing::data(&res)["post"], post);
+    }
+
+    #[actix_rt::test]
+    async fn databases_are_isolated() {
+        let api = TestApi::new().await;
+        let res = api.execute("{ posts { id } }", json!({})).await;
+        assert_eq!(testing::data(&res), json!({ "posts": [] }));
+    }
+
+    #[actix_rt::test]
+    async fn fails_to_update_missing_post() {
+        let api = TestApi::new().await;
+        let res = api
+            .execute(
+                "mutation UpdatePost($input: UpdatePostInput!) { updatePost(input: $input) { id } }",
+                json!({
+                    "input": {
+                        "id": "00000000-0000-0000-0000-000000000000",
+                        "title": "Title",
+                        "content": "Content",
+                    }
+                }),
+            )
+            .await;
+
+        assert_eq!(testing::reasons(&res), vec!["POST_NOT_FOUND"]);
+    }
+
+    #[actix_rt::test]
+    async fn creates_posts_atomically_in_order() {
+        let api = TestApi::new().await;
+
+        let res = api
+            .execute(
+                "mutation CreatePosts($inputs: [CreatePostInput!]!) {
+                    createPosts(inputs: $inputs, atomic: true) {
+                        committed items { post { title } error { reason } }
+                    }
+                }",
+                json!({ "inputs": [
+                    { "title": "First", "content": "Content" },
+                    { "title": "Second", "content": "Content" },
+                ] }),
+            )
+            .await;
+
+        assert_eq!(
+            testing::data(&res)["createPosts"],
+            json!({
+                "committed": true,
+                "items": [
+                    { "post": { "title": "First" }, "error": null },
+                    { "post": { "title": "Second" }, "error": null },
+                ],
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn rolls_back_atomic_bulk_delete() {
+        let api = TestApi::new().await;
+        let post = create_post(&api, "Title").await;
+
+        let res = api
+            .execute(
+                "mutation DeletePosts($ids: [PostId!]!) {
+                    deletePosts(ids: $ids, atomic: true) { committed items { error { reason } } }
+                }",
+                json!({ "ids": [post["id"], "00000000-0000-0000-0000-000000000000"] }),
+            )
+            .await;
+        let data = testing::data(&res);
+
+        assert_eq!(data["deletePosts"]["committed"], json!(false));
+        assert_eq!(
+            data["deletePosts"]["items"][1]["error"]["reason"],
+            json!("POST_NOT_FOUND")
+        );
+
+        let res = api.execute("{ posts { id } }", json!({})).await;
+        assert_eq!(testing::data(&res)["posts"], json!([{ "id": post["id"] }]));
+    }
+
+    #[actix_rt::test]
+    async fn reports_duplicate_ids_in_bulk_update() {
+        let api = TestApi::new().await;
+        let post = create_post(&api, "Title").await;
+
+        let res = api
+            .execute(
+                "mutation UpdatePosts($inputs: [UpdatePostInput!]!) {
+                    updatePosts(inputs: $inputs) {
+                        committed items { post { title } error { reason } }
+                    }
+                }",
+                json!({ "inputs": [
+                    { "id": post["id"], "title": "First", "content": "Content" },
+                    { "id": post["id"], "title": "Second", "content": "Content" },
+                ] }),
+            )
+            .await;
+        let data = testing::data(&res);
+
+        assert_eq!(data["updatePosts"]["committed"], json!(true));
+        assert_eq!(
+            data["updatePosts"]["items"][0]["post"]["title"],
+            json!("First")
+        );
+        assert_eq!(
+            data["updatePosts"]["items"][1]["error"]["reason"],
+            json!("DUPLICATE_ID")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn rejects_too_many_items_in_bulk_delete() {
+        let api = TestApi::new().await;
+        let ids = vec![json!("00000000-0000-0000-0000-000000000000"); post_bulk::MAX_ITEMS + 1];
+
+        let res = api
+            .execute(
+                "mutation DeletePosts($ids: [PostId!]!) {
+                    deletePosts(ids: $ids) { committed }
+                }",
+                json!({ "ids": ids }),
+            )
+            .await;
+
+        assert_eq!(testing::reasons(&res), vec!["TOO_MANY_ITEMS"]);
+    }
 }
@@ -1,14 +1,27 @@
+#[macro_use]
+extern crate lazy_static;
+
 #[macro_use]
 mod log;
 
+mod app;
 mod env;
 mod gql;
+mod metrics;
 mod models;
 mod pg;
+mod rate_limit;
 mod server;
+mod tasks;
+mod telemetry;
+
+#[cfg(test)]
+mod testing;
+
+use app::App;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     log::init();
-    server::run().await
+    App::new().run().await
 }
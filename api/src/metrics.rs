@@ -0,0 +1,169 @@
+use actix_web::{web::Data, HttpResponse};
+use prometheus::{Encoder, TextEncoder};
+
+use crate::pg::PgPool;
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub async fn endpoint(pg: Data<PgPool>) -> HttpResponse {
+    self::pg::observe_pool(&pg);
+
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok()
+            .content_type(encoder.format_type())
+            .body(buffer),
+        Err(error) => {
+            error!(format!("Failed to encode metrics: {}", error));
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub mod http {
+    use std::time::Duration;
+
+    use actix_web::dev::ServiceResponse;
+    use prometheus::{
+        register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
+    };
+
+    use super::LATENCY_BUCKETS;
+
+    // Requests that didn't match any route are collapsed into a single label
+    // so random paths can't blow up the cardinality
+    const UNMATCHED_ROUTE: &str = "unmatched";
+
+    lazy_static! {
+        static ref REQUESTS: IntCounterVec = register_int_counter_vec!(
+            "http_requests_total",
+            "Number of HTTP requests by route and status",
+            &["route", "status"]
+        )
+        .expect("Failed to register http_requests_total metric");
+        static ref DURATION: HistogramVec = register_histogram_vec!(
+            "http_request_duration_seconds",
+            "HTTP request latency by route and status",
+            &["route", "status"],
+            LATENCY_BUCKETS.to_vec()
+        )
+        .expect("Failed to register http_request_duration_seconds metric");
+    }
+
+    pub fn observe<B>(res: &ServiceResponse<B>, elapsed: Duration) {
+        let route = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let status = res.status().as_u16().to_string();
+        let labels = &[route.as_str(), status.as_str()];
+
+        REQUESTS.with_label_values(labels).inc();
+        DURATION
+            .with_label_values(labels)
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+pub mod gql {
+    use std::time::Duration;
+
+    use prometheus::{
+        register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
+    };
+
+    use super::LATENCY_BUCKETS;
+
+    pub const ANONYMOUS_OPERATION: &str = "anonymous";
+    pub const NO_ERROR: &str = "none";
+
+    lazy_static! {
+        static ref OPERATIONS: IntCounterVec = register_int_counter_vec!(
+            "graphql_operations_total",
+            "Number of GraphQL operations by operation name and error reason",
+            &["operation", "reason"]
+        )
+        .expect("Failed to register graphql_operations_total metric");
+        static ref DURATION: HistogramVec = register_histogram_vec!(
+            "graphql_operation_duration_seconds",
+            "GraphQL operation latency by operation name and error reason",
+            &["operation", "reason"],
+            LATENCY_BUCKETS.to_vec()
+        )
+        .expect("Failed to register graphql_operation_duration_seconds metric");
+    }
+
+    pub fn observe(operation: &str, reason: &str, elapsed: Duration) {
+        let labels = &[operation, reason];
+
+        OPERATIONS.with_label_values(labels).inc();
+        DURATION
+            .with_label_values(labels)
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+pub mod pg {
+    use std::time::Duration;
+
+    use prometheus::{register_histogram_vec, register_int_gauge, HistogramVec, IntGauge};
+
+    use super::LATENCY_BUCKETS;
+    use crate::pg::PgPool;
+
+    lazy_static! {
+        static ref POOL_SIZE: IntGauge = register_int_gauge!(
+            "pg_pool_connections",
+            "Number of connections currently held by the pool"
+        )
+        .expect("Failed to register pg_pool_connections metric");
+        static ref POOL_IDLE: IntGauge = register_int_gauge!(
+            "pg_pool_idle_connections",
+            "Number of idle connections in the pool"
+        )
+        .expect("Failed to register pg_pool_idle_connections metric");
+        static ref POOL_WAITERS: IntGauge = register_int_gauge!(
+            "pg_pool_waiters",
+            "Number of queries waiting for a connection"
+        )
+        .expect("Failed to register pg_pool_waiters metric");
+        static ref QUERIES_IN_FLIGHT: IntGauge = register_int_gauge!(
+            "pg_queries_in_flight",
+            "Number of queries being executed or waiting for a connection"
+        )
+        .expect("Failed to register pg_queries_in_flight metric");
+        static ref QUERY_DURATION: HistogramVec = register_histogram_vec!(
+            "pg_query_duration_seconds",
+            "Postgres query latency by query and outcome",
+            &["query", "outcome"],
+            LATENCY_BUCKETS.to_vec()
+        )
+        .expect("Failed to register pg_query_duration_seconds metric");
+    }
+
+    pub fn query_started() {
+        QUERIES_IN_FLIGHT.inc();
+    }
+
+    pub fn query_finished(query: &str, ok: bool, elapsed: Duration) {
+        QUERIES_IN_FLIGHT.dec();
+        QUERY_DURATION
+            .with_label_values(&[query, if ok { "ok" } else { "error" }])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_pool(pool: &PgPool) {
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+        // SQLx doesn't expose the wait queue of the pool, but every in-flight query
+        // either holds a connection or waits for one, so the difference is what's waiting
+        let waiters = QUERIES_IN_FLIGHT.get() - (size - idle);
+
+        POOL_SIZE.set(size);
+        POOL_IDLE.set(idle);
+        POOL_WAITERS.set(waiters.max(0));
+    }
+}
@@ -1,12 +1,20 @@
 use sqlx::types::Uuid;
 
-#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Debug)]
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[sqlx(transparent)]
 pub struct PostId(Uuid);
 
+impl PostId {
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+}
+
 graphql::scalar!(PostId);
 
-#[derive(serde::Serialize, sqlx::FromRow, graphql::SimpleObject, Debug)]
+#[derive(
+    serde::Serialize, serde::Deserialize, sqlx::FromRow, graphql::SimpleObject, Clone, Debug,
+)]
 pub struct Post {
     pub id: PostId,
     pub title: String,
@@ -0,0 +1,16 @@
+use std::{future::Future, time::Instant};
+
+use tracing_futures::Instrument;
+
+use crate::{metrics, telemetry};
+
+pub async fn query<F, T>(name: &'static str, query: F) -> sqlx::Result<T>
+where
+    F: Future<Output = sqlx::Result<T>>,
+{
+    metrics::pg::query_started();
+    let start = Instant::now();
+    let res = query.instrument(telemetry::sql_span(name)).await;
+    metrics::pg::query_finished(name, res.is_ok(), start.elapsed());
+    res
+}
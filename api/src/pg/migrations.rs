@@ -0,0 +1,342 @@
+// Migrations are tracked in the same table `sqlx migrate` uses, with the same checksums,
+// so databases migrated by either of them stay compatible.
+
+use std::{collections::BTreeMap, fmt, io, time::Instant};
+
+use sha2::{Digest, Sha384};
+use sqlx::{Connection, Executor, PgConnection};
+
+use crate::pg::PgPool;
+
+const CREATE_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    success BOOLEAN NOT NULL,
+    checksum BYTEA NOT NULL,
+    execution_time BIGINT NOT NULL
+)";
+
+pub struct Migration {
+    pub version: i64,
+    pub description: String,
+    pub up: String,
+    // Migrations created before up/down pairs were introduced can't be reverted
+    pub down: Option<String>,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> Vec<u8> {
+        Sha384::digest(self.up.as_bytes()).to_vec()
+    }
+}
+
+pub enum State {
+    Applied,
+    Pending,
+    // Migration was edited after it had been applied
+    ChecksumMismatch,
+    // Migration failed half-way and the database needs manual attention
+    Failed,
+    // Migration is applied, but this build doesn't know about it, i.e. the database is ahead
+    Unknown,
+}
+
+impl State {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            State::Applied => "applied",
+            State::Pending => "pending",
+            State::ChecksumMismatch => "checksum mismatch",
+            State::Failed => "failed",
+            State::Unknown => "unknown",
+        }
+    }
+}
+
+pub struct Status {
+    pub version: i64,
+    pub description: String,
+    pub state: State,
+    pub installed_on: Option<String>,
+}
+
+pub enum Error {
+    Db(sqlx::Error),
+    Invalid(String),
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Error::Db(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Db(error) => write!(f, "{}", error),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// Migrations from `api/migrations` as of the build, see `build.rs`
+static EMBEDDED: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+// Postgres advisory lock key, so only one instance migrates the database at a time
+const LOCK: i64 = 0x6d69_6772_6174_65;
+
+pub fn embedded() -> io::Result<Vec<Migration>> {
+    parse(
+        EMBEDDED
+            .iter()
+            .map(|(file_name, sql)| (file_name.to_string(), sql.to_string())),
+    )
+}
+
+// Accepts `<version>_<description>.up.sql` + `.down.sql` pairs and plain `<version>_<description>.sql`
+fn parse(files: impl Iterator<Item = (String, String)>) -> io::Result<Vec<Migration>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut migrations: BTreeMap<i64, Migration> = BTreeMap::new();
+
+    for (file_name, sql) in files {
+        let (version, rest) = match file_name.find('_') {
+            Some(idx) => (&file_name[..idx], &file_name[idx + 1..]),
+            None => return Err(invalid(format!("Invalid migration name {}", file_name))),
+        };
+        let version: i64 = version
+            .parse()
+            .map_err(|_| invalid(format!("Invalid migration version in {}", file_name)))?;
+        let (description, is_down) = if let Some(rest) = rest.strip_suffix(".down.sql") {
+            (rest, true)
+        } else if let Some(rest) = rest.strip_suffix(".up.sql") {
+            (rest, false)
+        } else {
+            (rest.trim_end_matches(".sql"), false)
+        };
+
+        let migration = migrations.entry(version).or_insert_with(|| Migration {
+            version,
+            description: description.replace('_', " "),
+            up: String::new(),
+            down: None,
+        });
+        if is_down {
+            migration.down = Some(sql);
+        } else {
+            migration.up = sql;
+        }
+    }
+
+    for migration in migrations.values() {
+        if migration.up.is_empty() {
+            return Err(invalid(format!(
+                "Migration {} has a down script, but no up script",
+                migration.version
+            )));
+        }
+    }
+
+    Ok(migrations
+        .into_iter()
+        .map(|(_, migration)| migration)
+        .collect())
+}
+
+pub async fn status(pg: &PgPool, migrations: &[Migration]) -> Result<Vec<Status>, Error> {
+    let mut conn = pg.acquire().await?;
+    statuses(&mut conn, migrations).await
+}
+
+// Applies pending migrations while holding an advisory lock, so instances booting at the same time
+// wait for each other instead of racing. Advisory locks belong to a session, so everything runs
+// on the connection that holds the lock, which also keeps it working with a pool of one connection.
+pub async fn run(pg: &PgPool, migrations: &[Migration]) -> Result<Vec<i64>, Error> {
+    let mut conn = pg.acquire().await?;
+    lock(&mut conn).await?;
+    let res = apply(&mut conn, migrations).await;
+    unlock(&mut conn).await?;
+    res
+}
+
+// Reverts the latest applied migration or, when `to` is set, every migration applied after it
+pub async fn revert(
+    pg: &PgPool,
+    migrations: &[Migration],
+    to: Option<i64>,
+) -> Result<Vec<i64>, Error> {
+    let mut conn = pg.acquire().await?;
+    lock(&mut conn).await?;
+    let res = unapply(&mut conn, migrations, to).await;
+    unlock(&mut conn).await?;
+    res
+}
+
+async fn lock(conn: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(LOCK)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn unlock(conn: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(LOCK)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn statuses(conn: &mut PgConnection, migrations: &[Migration]) -> Result<Vec<Status>, Error> {
+    let mut res: BTreeMap<i64, Status> = migrations
+        .iter()
+        .map(|migration| {
+            let status = Status {
+                version: migration.version,
+                description: migration.description.to_owned(),
+                state: State::Pending,
+                installed_on: None,
+            };
+            (migration.version, status)
+        })
+        .collect();
+
+    for (version, description, success, checksum, installed_on) in applied(conn).await? {
+        let local = migrations
+            .iter()
+            .find(|migration| migration.version == version);
+        let state = match local {
+            _ if !success => State::Failed,
+            None => State::Unknown,
+            Some(migration) if migration.checksum() != checksum => State::ChecksumMismatch,
+            Some(_) => State::Applied,
+        };
+        res.insert(
+            version,
+            Status {
+                version,
+                description,
+                state,
+                installed_on: Some(installed_on),
+            },
+        );
+    }
+
+    Ok(res.into_iter().map(|(_, status)| status).collect())
+}
+
+// Each migration is applied in its own transaction, so a failure leaves the database
+// at the last successfully applied one
+async fn apply(conn: &mut PgConnection, migrations: &[Migration]) -> Result<Vec<i64>, Error> {
+    let mut pending = vec![];
+    for status in statuses(conn, migrations).await? {
+        match status.state {
+            State::Applied => (),
+            State::Pending => pending.push(status.version),
+            State::ChecksumMismatch => {
+                return Err(Error::Invalid(format!(
+                    "Migration {} was modified after it had been applied",
+                    status.version
+                )))
+            }
+            State::Failed => {
+                return Err(Error::Invalid(format!(
+                    "Migration {} failed previously. Fix the database manually first.",
+                    status.version
+                )))
+            }
+            State::Unknown => {
+                return Err(Error::Invalid(format!(
+                    "Database has migration {} applied, which is unknown to this build",
+                    status.version
+                )))
+            }
+        }
+    }
+
+    for version in &pending {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.version == *version)
+            .expect("Pending migration is not found");
+
+        let start = Instant::now();
+        let mut tx = conn.begin().await?;
+        (&mut tx).execute(migration.up.as_str()).await?;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+             VALUES ($1, $2, TRUE, $3, $4)",
+        )
+        .bind(migration.version)
+        .bind(&migration.description)
+        .bind(migration.checksum())
+        .bind(start.elapsed().as_nanos() as i64)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+    }
+
+    Ok(pending)
+}
+
+async fn unapply(
+    conn: &mut PgConnection,
+    migrations: &[Migration],
+    to: Option<i64>,
+) -> Result<Vec<i64>, Error> {
+    let mut applied: Vec<i64> = applied(conn)
+        .await?
+        .into_iter()
+        .map(|(version, ..)| version)
+        .collect();
+    applied.sort_unstable_by(|a, b| b.cmp(a));
+
+    let targets: Vec<i64> = match to {
+        Some(to) => {
+            if to != 0 && !applied.contains(&to) {
+                return Err(Error::Invalid(format!("Migration {} is not applied", to)));
+            }
+            applied
+                .into_iter()
+                .filter(|version| *version > to)
+                .collect()
+        }
+        None => applied.into_iter().take(1).collect(),
+    };
+
+    for version in &targets {
+        let down = migrations
+            .iter()
+            .find(|migration| migration.version == *version)
+            .and_then(|migration| migration.down.as_ref())
+            .ok_or_else(|| {
+                Error::Invalid(format!("Migration {} doesn't have a down script", version))
+            })?;
+
+        let mut tx = conn.begin().await?;
+        (&mut tx).execute(down.as_str()).await?;
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+            .bind(version)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+    }
+
+    Ok(targets)
+}
+
+async fn applied(
+    conn: &mut PgConnection,
+) -> sqlx::Result<Vec<(i64, String, bool, Vec<u8>, String)>> {
+    (&mut *conn).execute(CREATE_TABLE).await?;
+    sqlx::query_as(
+        "SELECT version, description, success, checksum, installed_on::TEXT
+         FROM _sqlx_migrations
+         ORDER BY version",
+    )
+    .fetch_all(conn)
+    .await
+}
@@ -1,4 +1,8 @@
+pub mod instrument;
+pub mod migrations;
 pub mod pool;
 pub mod queries;
 
 pub use sqlx::postgres::PgPool;
+
+pub type PgTx = sqlx::Transaction<'static, sqlx::Postgres>;
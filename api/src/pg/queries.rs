@@ -1,5 +1,11 @@
 pub mod post_create;
+pub mod post_create_many;
 pub mod post_delete;
+pub mod post_delete_many;
 pub mod post_get_all;
 pub mod post_get_by_id;
 pub mod post_update;
+pub mod post_update_many;
+pub mod post_upsert;
+pub mod rate_limit_prune;
+pub mod rate_limit_take;
@@ -0,0 +1,26 @@
+use crate::{
+    gql::inputs::CreatePostInput,
+    models::post::{Post, PostId},
+    pg::{instrument, PgTx},
+};
+
+// Posts are returned in the order of the inputs: `RETURNING` doesn't guarantee any order,
+// so ids are generated upfront and results are sorted by the ordinality of their input
+pub async fn exec(inputs: Vec<CreatePostInput>, tx: &mut PgTx) -> sqlx::Result<Vec<Post>> {
+    let (titles, contents): (Vec<String>, Vec<String>) = inputs
+        .into_iter()
+        .map(|input| (input.title, input.content))
+        .unzip();
+
+    instrument::query(
+        "post_create_many",
+        sqlx::query_file_as!(
+            Post,
+            "src/pg/queries/post_create_many.sql",
+            &titles,
+            &contents
+        )
+        .fetch_all(tx),
+    )
+    .await
+}
@@ -0,0 +1,31 @@
+WITH input AS (
+    SELECT
+        uuid_generate_v4() AS id,
+        title,
+        content,
+        idx
+    FROM UNNEST($1::TEXT[], $2::TEXT[]) WITH ORDINALITY AS input (title, content, idx)
+),
+inserted AS (
+    INSERT INTO posts (
+        id,
+        title,
+        content
+    )
+    SELECT
+        id,
+        title,
+        content
+    FROM input
+    RETURNING
+        id,
+        title,
+        content
+)
+SELECT
+    inserted.id AS "id!: PostId",
+    inserted.title AS "title!",
+    inserted.content AS "content!"
+FROM inserted
+JOIN input ON input.id = inserted.id
+ORDER BY input.idx
@@ -0,0 +1,17 @@
+use sqlx::types::Uuid;
+
+use crate::{
+    models::post::{Post, PostId},
+    pg::{instrument, PgTx},
+};
+
+// Only posts that existed are returned, in no particular order
+pub async fn exec(ids: &[PostId], tx: &mut PgTx) -> sqlx::Result<Vec<Post>> {
+    let ids: Vec<Uuid> = ids.iter().map(|id| *id.as_uuid()).collect();
+
+    instrument::query(
+        "post_delete_many",
+        sqlx::query_file_as!(Post, "src/pg/queries/post_delete_many.sql", &ids).fetch_all(tx),
+    )
+    .await
+}
@@ -0,0 +1,6 @@
+DELETE FROM posts
+WHERE id IN (SELECT UNNEST($1::UUID[]))
+RETURNING
+    id AS "id: PostId",
+    title,
+    content
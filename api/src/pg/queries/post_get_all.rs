@@ -1,10 +1,12 @@
 use crate::{
     models::post::{Post, PostId},
-    pg::PgPool,
+    pg::{instrument, PgPool},
 };
 
 pub async fn exec(db: &PgPool) -> sqlx::Result<Vec<Post>> {
-    sqlx::query_file_as!(Post, "src/pg/queries/post_get_all.sql")
-        .fetch_all(db)
-        .await
+    instrument::query(
+        "post_get_all",
+        sqlx::query_file_as!(Post, "src/pg/queries/post_get_all.sql").fetch_all(db),
+    )
+    .await
 }
@@ -1,10 +1,12 @@
 use crate::{
     models::post::{Post, PostId},
-    pg::PgPool,
+    pg::{instrument, PgPool},
 };
 
 pub async fn exec(id: PostId, db: &PgPool) -> sqlx::Result<Post> {
-    sqlx::query_file_as!(Post, "src/pg/queries/post_get_by_id.sql", id as _)
-        .fetch_one(db)
-        .await
+    instrument::query(
+        "post_get_by_id",
+        sqlx::query_file_as!(Post, "src/pg/queries/post_get_by_id.sql", id as _).fetch_one(db),
+    )
+    .await
 }
@@ -1,7 +1,7 @@
 use crate::{
     gql::inputs::UpdatePostInput,
     models::post::{Post, PostId},
-    pg::PgPool,
+    pg::{instrument, PgPool},
 };
 
 pub async fn exec(input: UpdatePostInput, db: &PgPool) -> sqlx::Result<Post> {
@@ -9,13 +9,16 @@ pub async fn exec(input: UpdatePostInput, db: &PgPool) -> sqlx::Result<Post> {
     let title = &input.title;
     let content = &input.content;
 
-    sqlx::query_file_as!(
-        Post,
-        "src/pg/queries/post_update.sql",
-        id as _,
-        title,
-        content
+    instrument::query(
+        "post_update",
+        sqlx::query_file_as!(
+            Post,
+            "src/pg/queries/post_update.sql",
+            id as _,
+            title,
+            content
+        )
+        .fetch_one(db),
     )
-    .fetch_one(db)
     .await
 }
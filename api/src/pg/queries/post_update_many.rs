@@ -0,0 +1,32 @@
+use sqlx::types::Uuid;
+
+use crate::{
+    gql::inputs::UpdatePostInput,
+    models::post::{Post, PostId},
+    pg::{instrument, PgTx},
+};
+
+// Only posts that exist are returned, in no particular order
+pub async fn exec(inputs: Vec<UpdatePostInput>, tx: &mut PgTx) -> sqlx::Result<Vec<Post>> {
+    let mut ids: Vec<Uuid> = Vec::with_capacity(inputs.len());
+    let mut titles = Vec::with_capacity(inputs.len());
+    let mut contents = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        ids.push(*input.id.as_uuid());
+        titles.push(input.title);
+        contents.push(input.content);
+    }
+
+    instrument::query(
+        "post_update_many",
+        sqlx::query_file_as!(
+            Post,
+            "src/pg/queries/post_update_many.sql",
+            &ids,
+            &titles,
+            &contents
+        )
+        .fetch_all(tx),
+    )
+    .await
+}
@@ -0,0 +1,10 @@
+UPDATE posts
+SET
+    title = input.title,
+    content = input.content
+FROM UNNEST($1::UUID[], $2::TEXT[], $3::TEXT[]) AS input (id, title, content)
+WHERE posts.id = input.id
+RETURNING
+    posts.id AS "id: PostId",
+    posts.title,
+    posts.content
@@ -0,0 +1,21 @@
+use sqlx::Done;
+
+use crate::{
+    models::post::Post,
+    pg::{instrument, PgTx},
+};
+
+pub async fn exec(post: &Post, tx: &mut PgTx) -> sqlx::Result<u64> {
+    instrument::query(
+        "post_upsert",
+        sqlx::query_file!(
+            "src/pg/queries/post_upsert.sql",
+            post.id.as_uuid(),
+            post.title,
+            post.content
+        )
+        .execute(tx),
+    )
+    .await
+    .map(|res| res.rows_affected())
+}
@@ -0,0 +1,10 @@
+INSERT INTO posts (
+    id,
+    title,
+    content
+)
+VALUES ($1, $2, $3)
+ON CONFLICT (id) DO UPDATE
+SET
+    title = EXCLUDED.title,
+    content = EXCLUDED.content
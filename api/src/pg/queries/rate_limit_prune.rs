@@ -0,0 +1,21 @@
+use sqlx::Done;
+
+use crate::pg::{instrument, PgPool};
+
+// Drops buckets of the given budget that are full again: they're no different from missing ones.
+// Returns the number of dropped buckets.
+pub async fn exec(budget: &str, capacity: f64, refill_rate: f64, db: &PgPool) -> sqlx::Result<u64> {
+    let keys = format!("{}:%", budget);
+    instrument::query(
+        "rate_limit_prune",
+        sqlx::query_file!(
+            "src/pg/queries/rate_limit_prune.sql",
+            keys,
+            capacity,
+            refill_rate
+        )
+        .execute(db),
+    )
+    .await
+    .map(|res| res.rows_affected())
+}
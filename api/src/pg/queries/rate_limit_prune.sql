@@ -0,0 +1,4 @@
+DELETE FROM rate_limit_buckets
+WHERE
+    key LIKE $1
+    AND tokens + EXTRACT(EPOCH FROM NOW() - updated_at) * $3 >= $2
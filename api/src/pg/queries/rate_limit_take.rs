@@ -0,0 +1,18 @@
+use crate::pg::{instrument, PgPool};
+
+// Refills the bucket and takes a token from it if there's one.
+// Returns amount of tokens that were available before taking.
+pub async fn exec(key: &str, capacity: f64, refill_rate: f64, db: &PgPool) -> sqlx::Result<f64> {
+    instrument::query(
+        "rate_limit_take",
+        sqlx::query_file!(
+            "src/pg/queries/rate_limit_take.sql",
+            key,
+            capacity,
+            refill_rate
+        )
+        .fetch_one(db),
+    )
+    .await
+    .map(|row| row.available)
+}
@@ -0,0 +1,30 @@
+WITH bucket AS (
+    SELECT LEAST(
+        $2,
+        COALESCE(
+            (
+                SELECT tokens + EXTRACT(EPOCH FROM NOW() - updated_at) * $3
+                FROM rate_limit_buckets
+                WHERE key = $1
+                FOR UPDATE
+            ),
+            $2
+        )
+    ) AS tokens
+)
+INSERT INTO rate_limit_buckets (
+    key,
+    tokens,
+    updated_at
+)
+SELECT
+    $1,
+    CASE WHEN tokens >= 1 THEN tokens - 1 ELSE tokens END,
+    NOW()
+FROM bucket
+ON CONFLICT (key) DO UPDATE
+SET
+    tokens = EXCLUDED.tokens,
+    updated_at = EXCLUDED.updated_at
+RETURNING
+    (SELECT tokens FROM bucket) AS "available!"
@@ -0,0 +1,458 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{http::header::HeaderName, HttpRequest};
+
+use crate::{
+    env,
+    pg::{queries as db, PgPool},
+};
+
+// Buckets that are full again are dropped at most this often, so pruning doesn't run on every request
+const MEMORY_STORE_PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+// Hard cap on the number of buckets, so requests from many addresses can't exhaust memory
+const MEMORY_STORE_CAPACITY: usize = 100_000;
+// Share of least recently used buckets evicted at once when the cap is hit
+const MEMORY_STORE_EVICTION_RATIO: usize = 10;
+// Full buckets are deleted from the table at most this often by each instance
+const PG_STORE_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Clone, Copy)]
+pub enum Budget {
+    Query,
+    Mutation,
+}
+
+impl Budget {
+    fn to_str(&self) -> &'static str {
+        match self {
+            Budget::Query => "query",
+            Budget::Mutation => "mutation",
+        }
+    }
+}
+
+// Token bucket parameters: bucket holds up to `capacity` tokens
+// and gets `refill_rate` tokens back every second
+#[derive(Clone, Copy)]
+pub struct Rate {
+    pub capacity: f64,
+    pub refill_rate: f64,
+}
+
+impl Rate {
+    // Zero refill rate would make retry delays infinite and a bucket smaller than
+    // a single token would reject every request, so both are refused at boot
+    fn new(budget: Budget, capacity: f64, refill_rate: f64) -> Self {
+        if !(capacity.is_finite() && capacity >= 1.0) {
+            panic!(
+                "Burst of {} rate limit must be at least 1. Got: {}",
+                budget.to_str(),
+                capacity
+            );
+        }
+        if !(refill_rate.is_finite() && refill_rate > 0.0) {
+            panic!(
+                "Rate of {} rate limit must be greater than 0. Got: {}",
+                budget.to_str(),
+                refill_rate
+            );
+        }
+        Self {
+            capacity,
+            refill_rate,
+        }
+    }
+}
+
+// Who gets charged for a request
+pub enum Subject {
+    Ip(IpAddr),
+    Unknown,
+    // TODO: Add `User(UserId)` and prefer it over `Ip` once requests are authenticated
+}
+
+impl Subject {
+    fn to_key(&self) -> String {
+        match self {
+            Subject::Ip(ip) => format!("ip:{}", ip),
+            Subject::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+enum Store {
+    Memory(Mutex<MemoryStore>),
+    Postgres(PgStore),
+}
+
+struct MemoryStore {
+    buckets: HashMap<String, Bucket>,
+    pruned_at: Instant,
+}
+
+struct PgStore {
+    pg: PgPool,
+    pruned_at: Mutex<Instant>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    full_at: Instant,
+}
+
+pub struct Limiter {
+    store: Store,
+    queries: Rate,
+    mutations: Rate,
+    // Peers whose `X-Forwarded-For` is trusted, e.g. a load balancer in front of the API
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Limiter {
+    // Returns `None` when rate limiting is disabled
+    pub fn from_env(pg: PgPool) -> Option<Self> {
+        let store = match env::api_rate_limit_store().as_str() {
+            "memory" => Store::Memory(Mutex::new(MemoryStore {
+                buckets: HashMap::new(),
+                pruned_at: Instant::now(),
+            })),
+            "postgres" => Store::Postgres(PgStore {
+                pg,
+                pruned_at: Mutex::new(Instant::now()),
+            }),
+            "disabled" => return None,
+            store => panic!(
+                "API_RATE_LIMIT_STORE must be one of memory, postgres or disabled. Got: {}",
+                store
+            ),
+        };
+        Some(Self {
+            store,
+            queries: Rate::new(
+                Budget::Query,
+                env::api_rate_limit_query_burst(),
+                env::api_rate_limit_query_rate(),
+            ),
+            mutations: Rate::new(
+                Budget::Mutation,
+                env::api_rate_limit_mutation_burst(),
+                env::api_rate_limit_mutation_rate(),
+            ),
+            trusted_proxies: env::api_trusted_proxies(),
+        })
+    }
+
+    pub fn subject(&self, req: &HttpRequest) -> Subject {
+        let peer = match req.peer_addr() {
+            Some(addr) => addr.ip(),
+            None => return Subject::Unknown,
+        };
+        let forwarded_for: Vec<&str> = req
+            .headers()
+            .get_all(HeaderName::from_static(X_FORWARDED_FOR))
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        Subject::Ip(client_ip(
+            peer,
+            &forwarded_for.join(","),
+            &self.trusted_proxies,
+        ))
+    }
+
+    pub async fn take(&self, subject: &Subject, budget: Budget) -> Decision {
+        let rate = match budget {
+            Budget::Query => self.queries,
+            Budget::Mutation => self.mutations,
+        };
+        let key = format!("{}:{}", budget.to_str(), subject.to_key());
+
+        let available = match &self.store {
+            Store::Memory(buckets) => Limiter::take_from_memory(buckets, key, &rate),
+            Store::Postgres(store) => {
+                self.prune_postgres(store).await;
+                match db::rate_limit_take::exec(&key, rate.capacity, rate.refill_rate, &store.pg)
+                    .await
+                {
+                    Ok(available) => available,
+                    Err(error) => {
+                        // Failing open: it's better to let a few extra requests in than to reject all of them
+                        error!("Failed to take rate limit token", "key": key, "error": error);
+                        return Decision::Allowed;
+                    }
+                }
+            }
+        };
+
+        if available >= 1.0 {
+            Decision::Allowed
+        } else {
+            Decision::Limited {
+                retry_after: Duration::from_secs_f64((1.0 - available) / rate.refill_rate),
+            }
+        }
+    }
+
+    // Every client address leaves a row behind, so full buckets are deleted once in a while.
+    // Those are the same as missing ones, so deleting them never changes a decision.
+    async fn prune_postgres(&self, store: &PgStore) {
+        {
+            let now = Instant::now();
+            let mut pruned_at = store
+                .pruned_at
+                .lock()
+                .expect("Rate limiter mutex is poisoned");
+            if now.duration_since(*pruned_at) < PG_STORE_PRUNE_INTERVAL {
+                return;
+            }
+            *pruned_at = now;
+        }
+        for (budget, rate) in &[
+            (Budget::Query, self.queries),
+            (Budget::Mutation, self.mutations),
+        ] {
+            if let Err(error) = db::rate_limit_prune::exec(
+                budget.to_str(),
+                rate.capacity,
+                rate.refill_rate,
+                &store.pg,
+            )
+            .await
+            {
+                warn!("Failed to prune rate limit buckets", "budget": budget.to_str(), "error": error);
+            }
+        }
+    }
+
+    fn take_from_memory(store: &Mutex<MemoryStore>, key: String, rate: &Rate) -> f64 {
+        let now = Instant::now();
+        let mut store = store.lock().expect("Rate limiter mutex is poisoned");
+
+        if now.duration_since(store.pruned_at) >= MEMORY_STORE_PRUNE_INTERVAL {
+            store.buckets.retain(|_, bucket| bucket.full_at > now);
+            store.pruned_at = now;
+        }
+        if store.buckets.len() >= MEMORY_STORE_CAPACITY && !store.buckets.contains_key(&key) {
+            evict_least_recently_used(&mut store.buckets);
+        }
+
+        let bucket = store.buckets.entry(key).or_insert(Bucket {
+            tokens: rate.capacity,
+            updated_at: now,
+            full_at: now,
+        });
+        let refilled = now.duration_since(bucket.updated_at).as_secs_f64() * rate.refill_rate;
+        let available = (bucket.tokens + refilled).min(rate.capacity);
+
+        bucket.tokens = if available >= 1.0 {
+            available - 1.0
+        } else {
+            available
+        };
+        bucket.updated_at = now;
+        bucket.full_at =
+            now + Duration::from_secs_f64((rate.capacity - bucket.tokens) / rate.refill_rate);
+
+        available
+    }
+}
+
+// Evicts a batch of buckets at once, so a flood of new addresses doesn't pay for a scan on every request
+fn evict_least_recently_used(buckets: &mut HashMap<String, Bucket>) {
+    let mut by_age: Vec<(Instant, String)> = buckets
+        .iter()
+        .map(|(key, bucket)| (bucket.updated_at, key.to_owned()))
+        .collect();
+    by_age.sort_unstable();
+    let count = (buckets.len() / MEMORY_STORE_EVICTION_RATIO).max(1);
+    for (_, key) in by_age.into_iter().take(count) {
+        buckets.remove(&key);
+    }
+}
+
+// Proxies append the address they got a request from to `X-Forwarded-For`, so the header is
+// walked from the right and the first address that isn't a trusted proxy is the client.
+// Anything to the left of it is set by the client itself and can't be trusted.
+fn client_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+    let mut client = peer;
+    for entry in forwarded_for.rsplit(',') {
+        match entry.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDb;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn pg_limiter(pg: PgPool, rate: Rate) -> Limiter {
+        Limiter {
+            store: Store::Postgres(PgStore {
+                pg,
+                pruned_at: Mutex::new(Instant::now()),
+            }),
+            queries: rate,
+            mutations: rate,
+            trusted_proxies: vec![],
+        }
+    }
+
+    async fn bucket_keys(db: &TestDb) -> Vec<String> {
+        sqlx::query_as::<_, (String,)>("SELECT key FROM rate_limit_buckets ORDER BY key")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(key,)| key)
+            .collect()
+    }
+
+    #[test]
+    fn client_ip_ignores_header_from_untrusted_peers() {
+        let trusted = [ip("10.0.0.1")];
+        assert_eq!(
+            client_ip(ip("203.0.113.7"), "198.51.100.1", &trusted),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn client_ip_takes_rightmost_untrusted_address() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), "1.2.3.4, 198.51.100.1, 10.0.0.2", &trusted),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_last_valid_hop() {
+        let trusted = [ip("10.0.0.1")];
+        assert_eq!(client_ip(ip("10.0.0.1"), "", &trusted), ip("10.0.0.1"));
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), "unknown, 198.51.100.1", &trusted),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), "198.51.100.1, garbage", &trusted),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn eviction_drops_least_recently_used_buckets() {
+        let now = Instant::now();
+        let mut buckets = HashMap::new();
+        for idx in 0..20 {
+            let at = now + Duration::from_secs(idx);
+            buckets.insert(
+                format!("key:{}", idx),
+                Bucket {
+                    tokens: 0.0,
+                    updated_at: at,
+                    full_at: at,
+                },
+            );
+        }
+        evict_least_recently_used(&mut buckets);
+        assert_eq!(buckets.len(), 18);
+        assert!(!buckets.contains_key("key:0"));
+        assert!(!buckets.contains_key("key:1"));
+        assert!(buckets.contains_key("key:2"));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be greater than 0")]
+    fn zero_rate_is_refused() {
+        Rate::new(Budget::Query, 10.0, 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be at least 1")]
+    fn burst_below_one_is_refused() {
+        Rate::new(Budget::Mutation, 0.5, 1.0);
+    }
+
+    #[actix_rt::test]
+    async fn postgres_store_limits_each_bucket() {
+        let db = TestDb::new().await;
+        let limiter = pg_limiter(db.pool.clone(), Rate::new(Budget::Query, 2.0, 0.001));
+        let subject = Subject::Ip(ip("198.51.100.1"));
+
+        for _ in 0..2 {
+            let decision = limiter.take(&subject, Budget::Query).await;
+            assert!(matches!(decision, Decision::Allowed));
+        }
+        let decision = limiter.take(&subject, Budget::Query).await;
+        assert!(matches!(decision, Decision::Limited { .. }));
+
+        // Budgets and subjects have buckets of their own
+        let decision = limiter.take(&subject, Budget::Mutation).await;
+        assert!(matches!(decision, Decision::Allowed));
+        let other = Subject::Ip(ip("198.51.100.2"));
+        let decision = limiter.take(&other, Budget::Query).await;
+        assert!(matches!(decision, Decision::Allowed));
+    }
+
+    #[actix_rt::test]
+    async fn postgres_store_prunes_full_buckets() {
+        let db = TestDb::new().await;
+        let limiter = pg_limiter(db.pool.clone(), Rate::new(Budget::Query, 1.0, 0.001));
+        for subject in &["198.51.100.1", "198.51.100.2"] {
+            limiter.take(&Subject::Ip(ip(subject)), Budget::Query).await;
+        }
+        sqlx::query(
+            "UPDATE rate_limit_buckets SET updated_at = NOW() - INTERVAL '1 hour' WHERE key = $1",
+        )
+        .bind("query:ip:198.51.100.1")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        // Pruning isn't due yet
+        limiter
+            .take(&Subject::Ip(ip("198.51.100.3")), Budget::Query)
+            .await;
+        assert_eq!(bucket_keys(&db).await.len(), 3);
+
+        if let Store::Postgres(store) = &limiter.store {
+            *store.pruned_at.lock().unwrap() =
+                Instant::now().checked_sub(PG_STORE_PRUNE_INTERVAL).unwrap();
+        }
+        limiter
+            .take(&Subject::Ip(ip("198.51.100.2")), Budget::Query)
+            .await;
+        assert_eq!(
+            bucket_keys(&db).await,
+            vec!["query:ip:198.51.100.2", "query:ip:198.51.100.3"]
+        );
+    }
+}
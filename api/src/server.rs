@@ -1,11 +1,41 @@
-use actix_cors::Cors;
-use actix_web::{guard, http::header, web, App, HttpResponse, HttpServer};
+use std::time::Instant;
 
-use crate::{env, gql, pg};
+use actix_web::{dev::Service, guard, middleware, web, App, HttpResponse, HttpServer};
+use futures::future::{self, Either};
+use tracing_futures::Instrument;
 
-pub async fn run() -> std::io::Result<()> {
+use crate::{env, gql, metrics, pg, rate_limit, telemetry};
+
+mod cors;
+mod shutdown;
+mod tls;
+
+pub use shutdown::Outcome;
+
+pub async fn run(migrate: bool) -> std::io::Result<Outcome> {
     let pg = pg::pool::new().await;
+    if migrate {
+        migrate_database(&pg).await?;
+    }
     let gql = gql::schema::new();
+    let restricted_gql = gql::schema::restricted();
+    let drain = web::Data::new(shutdown::Drain::new());
+    let shutdown = shutdown::Shutdown {
+        drain: drain.clone(),
+        pg: pg.clone(),
+        grace: env::api_shutdown_grace(),
+        timeout: env::api_shutdown_timeout(),
+    };
+    let cors = cors::Policy::from_env();
+    let limiter = web::Data::new(rate_limit::Limiter::from_env(pg.clone()));
+    let batch_limit = web::Data::new(gql::http::api::BatchLimit::from_env());
+    let introspection = web::Data::new(gql::http::api::Introspection::from_env());
+    let explorer = gql::http::explorer::Explorer::from_env(&introspection).map(web::Data::new);
+    let tls = tls::Tls::from_env()?;
+    let redirect = match &tls {
+        Some(tls) if tls.redirect => Some(tls::Redirect::new(tls.port)),
+        Some(_) | None => None,
+    };
 
     let addr = format!(
         "{host}:{port}",
@@ -13,42 +43,105 @@ pub async fn run() -> std::io::Result<()> {
         port = env::api_port()
     );
 
-    HttpServer::new(move || {
-        let web_app = format!(
-            "http://{host}:{port}",
-            host = env::web_host(),
-            port = env::web_port()
-        );
-        let cors = Cors::default()
-            .allowed_origin(&web_app)
-            .allowed_methods(vec!["POST"])
-            .allowed_header(header::CONTENT_TYPE)
-            .max_age(3600);
+    let server = HttpServer::new(move || {
         let health_path = &env::api_health_path();
+        let readiness_path = &env::api_readiness_path();
+        let metrics_path = &env::api_metrics_path();
         let gql_path = &env::api_graphql_path();
 
         let app = App::new()
             .data(pg.clone())
             .data(gql.clone())
-            .wrap(cors)
+            .data(restricted_gql.clone())
+            .app_data(introspection.clone())
+            .app_data(drain.clone())
+            .app_data(limiter.clone())
+            .app_data(batch_limit.clone())
+            .wrap_fn({
+                let redirect = redirect.clone();
+                move |req, srv| match redirect
+                    .as_ref()
+                    .and_then(|redirect| redirect.response(&req))
+                {
+                    Some(res) => Either::Left(future::ok(req.into_response(res))),
+                    None => Either::Right(srv.call(req)),
+                }
+            })
+            .wrap(cors.middleware())
+            .wrap(middleware::Compress::default())
+            .wrap_fn(|req, srv| {
+                let start = Instant::now();
+                let res = srv.call(req);
+                async move {
+                    let res = res.await?;
+                    metrics::http::observe(&res, start.elapsed());
+                    Ok(res)
+                }
+            })
+            .wrap_fn(|req, srv| {
+                let span = telemetry::http_span(&req);
+                let res = srv.call(req);
+                async move {
+                    let res = res.await?;
+                    tracing::Span::current().record("http.status_code", &res.status().as_u16());
+                    Ok(res)
+                }
+                .instrument(span)
+            })
             .route(health_path, web::get().to(|| HttpResponse::NoContent()))
+            .route(readiness_path, web::get().to(shutdown::readiness))
+            .route(metrics_path, web::get().to(metrics::endpoint))
             .service(
                 web::resource(gql_path)
-                    .guard(guard::Post())
+                    .guard(guard::Any(guard::Get()).or(guard::Post()))
                     .to(gql::http::api::endpoint),
             );
 
-        #[cfg(debug_assertions)]
-        return app.service(
-            web::resource("/")
-                .guard(guard::Get())
-                .to(gql::http::playground::endpoint),
-        );
-
-        #[cfg(not(debug_assertions))]
-        return app;
+        match &explorer {
+            Some(explorer) => app
+                .app_data(explorer.clone())
+                .route(&explorer.path, web::get().to(gql::http::explorer::endpoint)),
+            None => app,
+        }
     })
-    .bind(&addr)?
-    .run()
-    .await
+    .bind(&addr)?;
+
+    let server = match &tls {
+        Some(tls) => {
+            tls.reload_on_sighup()?;
+            server.bind_rustls(
+                format!("{host}:{port}", host = env::api_host(), port = tls.port),
+                tls.config(),
+            )?
+        }
+        None => server,
+    }
+    .disable_signals()
+    .shutdown_timeout((shutdown.timeout + shutdown::ACTIX_TIMEOUT_SLACK).as_secs())
+    .run();
+
+    let outcome = shutdown.on_signal(server.clone()).await?;
+    server.await?;
+    Ok(outcome)
+}
+
+// Refuses to start when the database has migrations this binary doesn't know about,
+// since the schema might be incompatible with the queries it was built against
+async fn migrate_database(pg: &pg::PgPool) -> std::io::Result<()> {
+    let migrations = pg::migrations::embedded()?;
+    match pg::migrations::run(pg, &migrations).await {
+        Ok(applied) => {
+            for version in applied {
+                info!("Applied migration", "version": version);
+            }
+            Ok(())
+        }
+        Err(error) => {
+            error!("Failed to migrate database", "error": error.to_string());
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                error.to_string(),
+            ))
+        }
+    }
 }
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use actix_cors::Cors;
+use actix_web::http::Method;
+
+use crate::env;
+
+#[derive(Clone)]
+pub struct Policy {
+    origins: Arc<Vec<Origin>>,
+    allowed_headers: Vec<String>,
+    credentials: bool,
+    max_age: usize,
+}
+
+impl Policy {
+    // Entries that can't be parsed are logged and skipped, so a typo in one origin
+    // doesn't lock out the rest of them
+    pub fn from_env() -> Self {
+        let scheme = env::api_cors_scheme();
+        let origins = match env::api_cors_origins() {
+            Some(origins) => origins,
+            None => vec![format!(
+                "{host}:{port}",
+                host = env::web_host(),
+                port = env::web_port()
+            )],
+        };
+        let origins = origins
+            .iter()
+            .filter_map(|entry| match Origin::parse(entry, Some(&scheme)) {
+                Ok(origin) => Some(origin),
+                Err(reason) => {
+                    error!(
+                        "Invalid CORS origin is ignored",
+                        "origin": entry,
+                        "reason": reason
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            origins: Arc::new(origins),
+            allowed_headers: env::api_cors_allowed_headers(),
+            credentials: env::api_cors_credentials(),
+            max_age: env::api_cors_max_age(),
+        }
+    }
+
+    pub fn middleware(&self) -> Cors {
+        let origins = self.origins.clone();
+
+        let mut cors = Cors::default()
+            .allowed_origin_fn(move |header, _| match header.to_str() {
+                Ok(origin) => Origin::is_allowed(origin, &origins),
+                Err(_) => false,
+            })
+            .allowed_methods(vec![Method::GET, Method::POST])
+            .max_age(self.max_age);
+        for header in &self.allowed_headers {
+            cors = cors.allowed_header(header.as_str());
+        }
+
+        if self.credentials {
+            cors.supports_credentials()
+        } else {
+            cors
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Origin {
+    scheme: String,
+    host: Host,
+    // Default port of the scheme when it's omitted, since browsers omit it in `Origin` header
+    port: u16,
+}
+
+#[derive(Debug)]
+enum Host {
+    Exact(String),
+    // `*.example.com` is stored as `.example.com`
+    AnySubdomainOf(String),
+}
+
+impl Origin {
+    // Accepts `[scheme://]host[:port]`, where host is either a domain, an IP
+    // or a wildcard subdomain pattern like `*.preview.example.com`
+    fn parse(input: &str, default_scheme: Option<&str>) -> Result<Self, String> {
+        let input = input.trim();
+        let (scheme, rest) = match input.find("://") {
+            Some(idx) => (&input[..idx], &input[idx + 3..]),
+            None => match default_scheme {
+                Some(scheme) => (scheme, input),
+                None => return Err("Scheme is missing".to_string()),
+            },
+        };
+
+        if scheme != "http" && scheme != "https" {
+            return Err(format!("Unsupported scheme \"{}\"", scheme));
+        }
+        if rest.contains('/') {
+            return Err("Origin must not contain a path".to_string());
+        }
+
+        let (host, port) = match rest.rfind(':') {
+            Some(idx) => match rest[idx + 1..].parse::<u16>() {
+                Ok(port) => (&rest[..idx], port),
+                Err(_) => return Err(format!("Invalid port \"{}\"", &rest[idx + 1..])),
+            },
+            None if scheme == "https" => (rest, 443),
+            None => (rest, 80),
+        };
+
+        let host = match host.strip_prefix("*.") {
+            Some(domain) => {
+                Origin::validate_domain(domain)?;
+                Host::AnySubdomainOf(format!(".{}", domain.to_lowercase()))
+            }
+            None => {
+                Origin::validate_domain(host)?;
+                Host::Exact(host.to_lowercase())
+            }
+        };
+
+        Ok(Self {
+            scheme: scheme.to_string(),
+            host,
+            port,
+        })
+    }
+
+    fn validate_domain(domain: &str) -> Result<(), String> {
+        if domain.is_empty() {
+            return Err("Host is empty".to_string());
+        }
+        for label in domain.split('.') {
+            if label.is_empty() {
+                return Err(format!("Host \"{}\" has an empty label", domain));
+            }
+            if !label
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-')
+            {
+                return Err(format!("Host \"{}\" contains invalid characters", domain));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, origin: &Origin) -> bool {
+        let host = match (&self.host, &origin.host) {
+            (Host::Exact(allowed), Host::Exact(host)) => allowed == host,
+            (Host::AnySubdomainOf(suffix), Host::Exact(host)) => {
+                host.len() > suffix.len() && host.ends_with(suffix.as_str())
+            }
+            (_, Host::AnySubdomainOf(_)) => false,
+        };
+        host && self.scheme == origin.scheme && self.port == origin.port
+    }
+
+    fn is_allowed(origin: &str, allowed: &[Origin]) -> bool {
+        let res = match Origin::parse(origin, None) {
+            Ok(Origin {
+                host: Host::AnySubdomainOf(_),
+                ..
+            }) => Err("Wildcards are not allowed in request origin".to_string()),
+            Ok(origin) => Ok(allowed.iter().any(|allowed| allowed.matches(&origin))),
+            Err(reason) => Err(reason),
+        };
+        // Origin is controlled by the client, so these are debug logs: anyone could flood warnings
+        match res {
+            Ok(true) => true,
+            Ok(false) => {
+                debug!("CORS origin is not allowed", "origin": origin);
+                false
+            }
+            Err(reason) => {
+                debug!(
+                    "CORS origin is invalid",
+                    "origin": origin,
+                    "reason": reason
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(input: &str) -> Origin {
+        Origin::parse(input, Some("http")).unwrap()
+    }
+
+    #[test]
+    fn parse_applies_default_scheme_and_port() {
+        let res = origin("Example.com");
+        assert_eq!(res.scheme, "http");
+        assert_eq!(res.port, 80);
+        assert!(matches!(res.host, Host::Exact(host) if host == "example.com"));
+
+        assert_eq!(origin("https://example.com").port, 443);
+        assert_eq!(origin("https://example.com:8443").port, 8443);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_origins() {
+        assert!(Origin::parse("example.com", None).is_err());
+        assert!(Origin::parse("ftp://example.com", None).is_err());
+        assert!(Origin::parse("http://example.com/path", None).is_err());
+        assert!(Origin::parse("http://example.com:port", None).is_err());
+        assert!(Origin::parse("http://exa_mple.com", None).is_err());
+        assert!(Origin::parse("http://example..com", None).is_err());
+        assert!(Origin::parse("http://", None).is_err());
+    }
+
+    #[test]
+    fn matches_default_port_explicitly_or_implicitly() {
+        assert!(origin("https://example.com:443").matches(&origin("https://example.com")));
+        assert!(origin("https://example.com").matches(&origin("https://example.com:443")));
+        assert!(origin("http://example.com").matches(&origin("http://example.com:80")));
+        assert!(!origin("https://example.com").matches(&origin("https://example.com:8443")));
+    }
+
+    #[test]
+    fn matches_requires_same_scheme() {
+        assert!(!origin("https://example.com").matches(&origin("http://example.com")));
+        assert!(!origin("http://example.com:443").matches(&origin("https://example.com")));
+    }
+
+    #[test]
+    fn matches_wildcard_subdomains_only() {
+        let allowed = origin("https://*.example.com");
+        assert!(allowed.matches(&origin("https://preview.example.com")));
+        assert!(allowed.matches(&origin("https://a.b.example.com")));
+        assert!(!allowed.matches(&origin("https://example.com")));
+        assert!(!allowed.matches(&origin("https://badexample.com")));
+    }
+
+    #[test]
+    fn is_allowed_rejects_wildcards_in_request_origin() {
+        let allowed = vec![origin("https://*.example.com")];
+        assert!(Origin::is_allowed("https://app.example.com", &allowed));
+        assert!(!Origin::is_allowed("https://*.example.com", &allowed));
+        assert!(!Origin::is_allowed("https://app.example.org", &allowed));
+        assert!(!Origin::is_allowed("null", &allowed));
+    }
+}
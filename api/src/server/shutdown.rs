@@ -0,0 +1,116 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use actix_web::{dev::Server, rt::time, web::Data, HttpResponse};
+
+use crate::pg::PgPool;
+
+pub enum Outcome {
+    // All in-flight requests finished within the timeout
+    Drained,
+    // Timeout expired and remaining connections were dropped
+    TimedOut,
+}
+
+impl Outcome {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Drained => 0,
+            // Same code `timeout(1)` uses, so deploy tooling can tell a forced shutdown apart
+            Outcome::TimedOut => 124,
+        }
+    }
+}
+
+pub struct Drain(AtomicBool);
+
+impl Drain {
+    pub fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    pub fn start(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub async fn readiness(drain: Data<Drain>) -> HttpResponse {
+    if drain.is_started() {
+        HttpResponse::ServiceUnavailable().finish()
+    } else {
+        HttpResponse::NoContent().finish()
+    }
+}
+
+// Actix force-closes connections once its own shutdown timeout expires and `stop(true)` resolves
+// as if everything was drained. So actix is given more time than `Shutdown::timeout`,
+// and it's the outer timeout that decides whether connections were dropped.
+pub const ACTIX_TIMEOUT_SLACK: Duration = Duration::from_secs(5);
+
+pub struct Shutdown {
+    pub drain: Data<Drain>,
+    pub pg: PgPool,
+    // Time between failing readiness checks and closing the listener,
+    // so load balancers stop routing traffic here first
+    pub grace: Duration,
+    pub timeout: Duration,
+}
+
+impl Shutdown {
+    // Waits for a termination signal, flips readiness, waits for the grace period, then stops
+    // accepting new connections and gives in-flight requests up to `timeout` to finish
+    // before closing the PG pool.
+    pub async fn on_signal(self, server: Server) -> io::Result<Outcome> {
+        let signal = signal().await?;
+
+        info!(
+            "Draining connections",
+            "signal": signal,
+            "grace": format!("{}s", self.grace.as_secs()),
+            "timeout": format!("{}s", self.timeout.as_secs())
+        );
+        self.drain.start();
+        time::delay_for(self.grace).await;
+
+        let outcome = match time::timeout(self.timeout, server.stop(true)).await {
+            Ok(()) => Outcome::Drained,
+            Err(_) => {
+                warn!("Drain timeout expired, dropping remaining connections");
+                server.stop(false).await;
+                Outcome::TimedOut
+            }
+        };
+
+        self.pg.close().await;
+        info!("PG pool closed");
+
+        Ok(outcome)
+    }
+}
+
+#[cfg(unix)]
+async fn signal() -> io::Result<&'static str> {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+    use futures::future::{self, Either};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+
+    match future::select(Box::pin(sigterm.recv()), Box::pin(sigint.recv())).await {
+        Either::Left(_) => Ok("SIGTERM"),
+        Either::Right(_) => Ok("SIGINT"),
+    }
+}
+
+#[cfg(windows)]
+async fn signal() -> io::Result<&'static str> {
+    actix_web::rt::signal::ctrl_c().await?;
+    Ok("CTRL-C")
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use actix_web::{dev::ServiceRequest, http::header, HttpResponse};
+use rustls::{
+    internal::pemfile,
+    sign::{self, CertifiedKey},
+    ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig,
+};
+
+use crate::env;
+
+pub struct Tls {
+    pub port: u16,
+    pub redirect: bool,
+    resolver: Arc<Resolver>,
+}
+
+impl Tls {
+    // Returns `None` when TLS is not configured
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let (cert, key) = match (env::api_tls_cert(), env::api_tls_key()) {
+            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+            (None, None) => return Ok(None),
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Both API_TLS_CERT and API_TLS_KEY must be set to enable TLS",
+                ))
+            }
+        };
+        let resolver = Resolver::new(cert, key)?;
+
+        Ok(Some(Self {
+            port: env::api_tls_port(),
+            redirect: env::api_tls_redirect(),
+            resolver: Arc::new(resolver),
+        }))
+    }
+
+    pub fn config(&self) -> ServerConfig {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.cert_resolver = self.resolver.clone();
+        config
+    }
+
+    // Certificate is swapped in place, so connections that are already established keep going
+    // and new handshakes pick up the new certificate
+    #[cfg(unix)]
+    pub fn reload_on_sighup(&self) -> io::Result<()> {
+        use actix_web::rt::{
+            self,
+            signal::unix::{signal, SignalKind},
+        };
+
+        let mut sighup = signal(SignalKind::hangup())?;
+        let resolver = self.resolver.clone();
+
+        rt::spawn(async move {
+            while let Some(()) = sighup.recv().await {
+                match resolver.reload() {
+                    Ok(()) => info!("TLS certificate reloaded"),
+                    Err(error) => error!(
+                        "Failed to reload TLS certificate. Keeping the current one.",
+                        "error": error
+                    ),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    pub fn reload_on_sighup(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Resolver {
+    cert: PathBuf,
+    key: PathBuf,
+    current: RwLock<CertifiedKey>,
+}
+
+impl Resolver {
+    fn new(cert: PathBuf, key: PathBuf) -> io::Result<Self> {
+        let current = RwLock::new(Resolver::load(&cert, &key)?);
+        Ok(Self { cert, key, current })
+    }
+
+    fn reload(&self) -> io::Result<()> {
+        let next = Resolver::load(&self.cert, &self.key)?;
+        *self
+            .current
+            .write()
+            .expect("TLS certificate lock is poisoned") = next;
+        Ok(())
+    }
+
+    fn load(cert: &PathBuf, key: &PathBuf) -> io::Result<CertifiedKey> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let certs = pemfile::certs(&mut BufReader::new(File::open(cert)?))
+            .map_err(|()| invalid(format!("Failed to parse certificate {}", cert.display())))?;
+
+        // `openssl` emits PKCS8 keys nowadays, but older tooling still produces RSA ones
+        let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(key)?))
+            .map_err(|()| invalid(format!("Failed to parse private key {}", key.display())))?;
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut BufReader::new(File::open(key)?))
+                .map_err(|()| invalid(format!("Failed to parse private key {}", key.display())))?;
+        }
+        let key = match keys.into_iter().next() {
+            Some(key) => key,
+            None => {
+                return Err(invalid(format!(
+                    "No private key found in {}",
+                    key.display()
+                )))
+            }
+        };
+        let key = sign::any_supported_type(&key)
+            .map_err(|()| invalid("Unsupported private key type".to_string()))?;
+
+        Ok(CertifiedKey::new(certs, Arc::new(key)))
+    }
+}
+
+impl ResolvesServerCert for Resolver {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        self.current.read().ok().map(|current| current.clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct Redirect {
+    port: u16,
+    // Probes usually hit plain HTTP, so these paths aren't redirected
+    exempt: Vec<String>,
+}
+
+impl Redirect {
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            exempt: vec![env::api_health_path(), env::api_readiness_path()],
+        }
+    }
+
+    pub fn response(&self, req: &ServiceRequest) -> Option<HttpResponse> {
+        let conn = req.connection_info();
+        if conn.scheme() != "http" || self.exempt.iter().any(|path| path == req.path()) {
+            return None;
+        }
+
+        let host = conn.host();
+        let host = match host.rfind(':') {
+            Some(idx) if !host[idx..].contains(']') => &host[..idx],
+            Some(_) | None => host,
+        };
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+
+        Some(
+            HttpResponse::PermanentRedirect()
+                .header(
+                    header::LOCATION,
+                    format!("https://{}:{}{}", host, self.port, path),
+                )
+                .finish(),
+        )
+    }
+}
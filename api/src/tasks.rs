@@ -0,0 +1,11 @@
+// One-off jobs run via subcommands of the api binary against the database of the current env
+
+pub mod migrations;
+pub mod posts;
+pub mod seed;
+
+use std::io;
+
+fn other(error: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
@@ -0,0 +1,65 @@
+use std::io;
+
+use crate::pg::{self, migrations};
+
+use super::other;
+
+pub async fn run() -> io::Result<()> {
+    let migrations = migrations::embedded()?;
+    let pg = pg::pool::new().await;
+    let applied = migrations::run(&pg, &migrations).await.map_err(other)?;
+    if applied.is_empty() {
+        eprintln!("Database is up to date");
+    }
+    for version in applied {
+        eprintln!("Applied {}", version);
+    }
+    Ok(())
+}
+
+pub async fn revert(to: Option<i64>) -> io::Result<()> {
+    let migrations = migrations::embedded()?;
+    let pg = pg::pool::new().await;
+    let reverted = migrations::revert(&pg, &migrations, to)
+        .await
+        .map_err(other)?;
+    if reverted.is_empty() {
+        eprintln!("Nothing to revert");
+    }
+    for version in reverted {
+        eprintln!("Reverted {}", version);
+    }
+    Ok(())
+}
+
+pub async fn status() -> io::Result<()> {
+    let migrations = migrations::embedded()?;
+    let pg = pg::pool::new().await;
+    let statuses = migrations::status(&pg, &migrations).await.map_err(other)?;
+
+    let width = statuses
+        .iter()
+        .map(|status| status.description.len())
+        .max()
+        .unwrap_or(0)
+        .max("DESCRIPTION".len());
+    println!(
+        "{:<14}  {:<width$}  {:<17}  {}",
+        "VERSION",
+        "DESCRIPTION",
+        "STATUS",
+        "INSTALLED ON",
+        width = width
+    );
+    for status in statuses {
+        println!(
+            "{:<14}  {:<width$}  {:<17}  {}",
+            status.version,
+            status.description,
+            status.state.to_str(),
+            status.installed_on.as_deref().unwrap_or("-"),
+            width = width
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    models::post::{Post, PostId},
+    pg::{self, queries as db},
+};
+
+use super::other;
+
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+pub enum Format {
+    // One JSON object per line
+    Jsonl,
+    // A directory with one `<id>.md` file per post: front-matter with id and title, then content
+    Markdown,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> io::Result<Self> {
+        match format {
+            "jsonl" => Ok(Format::Jsonl),
+            "markdown" | "md" => Ok(Format::Markdown),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported format {}. Use jsonl or markdown.", format),
+            )),
+        }
+    }
+}
+
+// When `out` is not set, JSON Lines are written to stdout. Markdown requires a directory.
+pub async fn export(format: Format, out: Option<&Path>) -> io::Result<()> {
+    let pg = pg::pool::new().await;
+    let mut posts = db::post_get_all::exec(&pg).await.map_err(other)?;
+    posts.sort_by(|a, b| a.id.as_uuid().cmp(b.id.as_uuid()));
+
+    match format {
+        Format::Jsonl => {
+            let mut lines = String::new();
+            for post in &posts {
+                lines.push_str(&serde_json::to_string(post)?);
+                lines.push('\n');
+            }
+            match out {
+                Some(out) => fs::write(out, lines)?,
+                None => io::stdout().write_all(lines.as_bytes())?,
+            }
+        }
+        Format::Markdown => {
+            let dir = out.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Markdown export requires an output directory",
+                )
+            })?;
+            fs::create_dir_all(dir)?;
+            for post in &posts {
+                fs::write(
+                    dir.join(format!("{}.md", post.id.as_uuid())),
+                    to_markdown(post)?,
+                )?;
+            }
+        }
+    }
+
+    eprintln!("Exported {} posts", posts.len());
+    Ok(())
+}
+
+// Posts imported from a file, along with whether the record carried a slug
+struct Record {
+    post: Post,
+    has_slug: bool,
+}
+
+// Posts are matched by id, so importing the same file twice doesn't change anything.
+// Posts have no slug column, so slugs in records can't be matched on: those are reported and ignored.
+pub async fn import(format: Format, path: &Path, dry_run: bool) -> io::Result<()> {
+    let records = match format {
+        Format::Jsonl => fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                from_jsonl(line)
+                    .map_err(|error| other(format!("{}:{}: {}", path.display(), idx + 1, error)))
+            })
+            .collect::<io::Result<Vec<_>>>()?,
+        Format::Markdown => {
+            let mut files = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .filter(|path| match path {
+                    Ok(path) => path.extension().map_or(false, |ext| ext == "md"),
+                    Err(_) => true,
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            files.sort();
+            files
+                .iter()
+                .map(|file| {
+                    from_markdown(&fs::read_to_string(file)?)
+                        .map_err(|error| other(format!("{}: {}", file.display(), error)))
+                })
+                .collect::<io::Result<Vec<_>>>()?
+        }
+    };
+
+    let with_slug = records.iter().filter(|record| record.has_slug).count();
+    if with_slug > 0 {
+        eprintln!(
+            "Warning: {} record(s) have a slug, which is ignored. Posts have no slugs and are matched by id only.",
+            with_slug
+        );
+    }
+    let posts = records.into_iter().map(|record| record.post);
+
+    let pg = pg::pool::new().await;
+    let existing: HashMap<_, _> = db::post_get_all::exec(&pg)
+        .await
+        .map_err(other)?
+        .into_iter()
+        .map(|post| (*post.id.as_uuid(), post))
+        .collect();
+
+    let mut changed = vec![];
+    let (mut created, mut updated, mut unchanged) = (0, 0, 0);
+    for post in posts {
+        let change = match existing.get(post.id.as_uuid()) {
+            None => {
+                created += 1;
+                "create"
+            }
+            Some(current) if current.title != post.title || current.content != post.content => {
+                updated += 1;
+                "update"
+            }
+            Some(_) => {
+                unchanged += 1;
+                continue;
+            }
+        };
+        println!("{:<6} {} {}", change, post.id.as_uuid(), post.title);
+        changed.push(post);
+    }
+
+    if !dry_run && !changed.is_empty() {
+        let mut tx = pg.begin().await.map_err(other)?;
+        for post in &changed {
+            db::post_upsert::exec(post, &mut tx).await.map_err(other)?;
+        }
+        tx.commit().await.map_err(other)?;
+    }
+
+    eprintln!(
+        "{}{} created, {} updated, {} unchanged",
+        if dry_run { "Dry run: " } else { "" },
+        created,
+        updated,
+        unchanged
+    );
+    Ok(())
+}
+
+fn to_markdown(post: &Post) -> io::Result<String> {
+    Ok(format!(
+        "{delimiter}\nid: {id}\ntitle: {title}\n{delimiter}\n\n{content}\n",
+        delimiter = FRONT_MATTER_DELIMITER,
+        id = post.id.as_uuid(),
+        // JSON string is a valid YAML scalar, so quotes and newlines in titles survive the trip
+        title = serde_json::to_string(&post.title)?,
+        content = post.content,
+    ))
+}
+
+fn from_jsonl(line: &str) -> Result<Record, serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    let has_slug = value.get("slug").is_some();
+    Ok(Record {
+        post: serde_json::from_value(value)?,
+        has_slug,
+    })
+}
+
+fn from_markdown(input: &str) -> Result<Record, String> {
+    let rest = input
+        .strip_prefix(FRONT_MATTER_DELIMITER)
+        .and_then(|rest| rest.strip_prefix('\n'))
+        .ok_or_else(|| "Front-matter is missing".to_string())?;
+    let end = rest
+        .find(&format!("\n{}\n", FRONT_MATTER_DELIMITER))
+        .ok_or_else(|| "Front-matter is not closed".to_string())?;
+    let (front_matter, content) = (
+        &rest[..end],
+        &rest[end + FRONT_MATTER_DELIMITER.len() + 2..],
+    );
+
+    let mut id = None;
+    let mut title = None;
+    let mut has_slug = false;
+    for line in front_matter.lines() {
+        let (key, value) = match line.find(':') {
+            Some(idx) => (line[..idx].trim(), line[idx + 1..].trim()),
+            None => return Err(format!("Invalid front-matter line: {}", line)),
+        };
+        match key {
+            "id" => id = Some(value.to_string()),
+            "title" if value.starts_with('"') => {
+                title = Some(serde_json::from_str(value).map_err(|error| error.to_string())?)
+            }
+            "title" => title = Some(value.to_string()),
+            "slug" => has_slug = true,
+            _ => (),
+        }
+    }
+
+    let id: PostId = serde_json::from_value(serde_json::Value::String(
+        id.ok_or_else(|| "Id is missing".to_string())?,
+    ))
+    .map_err(|error| format!("Invalid id: {}", error))?;
+
+    // Exported content is separated from front-matter by an empty line and ends with a newline
+    let content = content.strip_prefix('\n').unwrap_or(content);
+    let content = content.strip_suffix('\n').unwrap_or(content);
+
+    Ok(Record {
+        post: Post {
+            id,
+            title: title.ok_or_else(|| "Title is missing".to_string())?,
+            content: content.to_string(),
+        },
+        has_slug,
+    })
+}
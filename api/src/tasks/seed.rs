@@ -0,0 +1,103 @@
+use std::io;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    gql::inputs::CreatePostInput,
+    pg::{self, queries as db},
+};
+
+use super::other;
+
+// TODO: Seed users, comments and tags once they exist
+
+const WORDS: &[&str] = &[
+    "actor",
+    "async",
+    "borrow",
+    "browser",
+    "cargo",
+    "closure",
+    "compiler",
+    "component",
+    "crate",
+    "database",
+    "deploy",
+    "effect",
+    "future",
+    "graph",
+    "hook",
+    "lifetime",
+    "macro",
+    "module",
+    "mutation",
+    "pattern",
+    "pool",
+    "query",
+    "reducer",
+    "release",
+    "request",
+    "resolver",
+    "schema",
+    "server",
+    "signal",
+    "socket",
+    "stream",
+    "syntax",
+    "trait",
+    "type",
+    "variant",
+    "vector",
+    "webpack",
+    "worker",
+];
+
+// The same seed always produces the same posts, so bugs found against seeded data are reproducible.
+// ChaCha is used rather than `StdRng`, whose output may change between rand versions and platforms.
+pub async fn exec(count: usize, seed: u64) -> io::Result<()> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let inputs: Vec<_> = (0..count)
+        .map(|_| CreatePostInput {
+            title: sentence(&mut rng, 3, 7),
+            content: paragraphs(&mut rng),
+        })
+        .collect();
+
+    let pg = pg::pool::new().await;
+    let mut tx = pg.begin().await.map_err(other)?;
+    let posts = db::post_create_many::exec(inputs, &mut tx)
+        .await
+        .map_err(other)?;
+    tx.commit().await.map_err(other)?;
+
+    eprintln!("Seeded {} posts using seed {}", posts.len(), seed);
+    Ok(())
+}
+
+fn sentence(rng: &mut ChaCha8Rng, min: usize, max: usize) -> String {
+    let len = rng.gen_range(min, max + 1);
+    let words: Vec<&str> = (0..len)
+        .map(|_| *WORDS.choose(rng).expect("Word list is empty"))
+        .collect();
+    let sentence = words.join(" ");
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => sentence,
+    }
+}
+
+fn paragraphs(rng: &mut ChaCha8Rng) -> String {
+    let count = rng.gen_range(2, 5);
+    (0..count)
+        .map(|_| {
+            let sentences = rng.gen_range(3, 7);
+            (0..sentences)
+                .map(|_| format!("{}.", sentence(rng, 5, 12)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
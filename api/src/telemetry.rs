@@ -0,0 +1,225 @@
+// Spans are created unconditionally: for HTTP requests, GraphQL resolvers and SQL queries.
+// When API_OTLP_ENDPOINT is not set, there's no subscriber and spans are no-op.
+
+use actix_web::{dev::ServiceRequest, http::HeaderMap};
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    sdk::{propagation::TraceContextPropagator, trace::Config},
+    KeyValue,
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use crate::env;
+
+const SERVICE_NAME: &str = "rrd-api";
+const TRACEPARENT: &str = "traceparent";
+
+pub use opentelemetry_otlp::Uninstall as Guard;
+
+// Returned guard flushes pending spans on drop, so it must be held until the server exits
+pub fn init() -> Option<Guard> {
+    let endpoint = env::api_otlp_endpoint()?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let res = opentelemetry_otlp::new_pipeline()
+        .with_endpoint(&endpoint)
+        .with_trace_config(
+            Config::default().with_resource(opentelemetry::sdk::Resource::new(vec![
+                KeyValue::new("service.name", SERVICE_NAME),
+            ])),
+        )
+        .install();
+
+    match res {
+        Ok((tracer, guard)) => {
+            let subscriber =
+                Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+            if let Err(error) = tracing::subscriber::set_global_default(subscriber) {
+                error!(format!("Failed to set tracing subscriber: {}", error));
+                return None;
+            }
+            info!("Exporting traces", "endpoint": endpoint);
+            Some(guard)
+        }
+        Err(error) => {
+            error!(format!("Failed to install OTLP pipeline: {}", error));
+            None
+        }
+    }
+}
+
+pub fn http_span(req: &ServiceRequest) -> Span {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    let span = tracing::info_span!(
+        "HTTP request",
+        http.method = %req.method(),
+        http.target = %req.path(),
+        http.status_code = tracing::field::Empty,
+    );
+    span.set_parent(&parent);
+    span
+}
+
+pub fn sql_span(query: &'static str) -> Span {
+    tracing::info_span!("SQL query", db.system = "postgresql", db.operation = query)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        let value = self.0.get(key).and_then(|value| value.to_str().ok())?;
+        if key.eq_ignore_ascii_case(TRACEPARENT) && !is_valid_traceparent(value) {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+// W3C Trace Context `version-trace_id-parent_id-flags` in lowercase hex. Headers with the forbidden
+// `ff` version or all-zero ids are ignored, so such requests start a new trace instead.
+// Later versions may append fields, version `00` may not.
+fn is_valid_traceparent(value: &str) -> bool {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    match parts.as_slice() {
+        [version, trace_id, span_id, flags, rest @ ..] => {
+            is_hex(version, 2)
+                && *version != "ff"
+                && (*version != "00" || rest.is_empty())
+                && is_id(trace_id, 32)
+                && is_id(span_id, 16)
+                && is_hex(flags, 2)
+        }
+        _ => false,
+    }
+}
+
+fn is_id(value: &str, len: usize) -> bool {
+    is_hex(value, len) && value.bytes().any(|byte| byte != b'0')
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn traceparent(version: &str, trace_id: &str, span_id: &str, flags: &str) -> String {
+        format!("{}-{}-{}-{}", version, trace_id, span_id, flags)
+    }
+
+    #[test]
+    fn accepts_valid_traceparent() {
+        assert!(is_valid_traceparent(&traceparent(
+            "00", TRACE_ID, SPAN_ID, "01"
+        )));
+        assert!(is_valid_traceparent(&traceparent(
+            "00", TRACE_ID, SPAN_ID, "00"
+        )));
+        // Later versions may have more fields
+        assert!(is_valid_traceparent(&format!(
+            "{}-extra",
+            traceparent("01", TRACE_ID, SPAN_ID, "01")
+        )));
+    }
+
+    #[test]
+    fn rejects_bad_version() {
+        assert!(!is_valid_traceparent(&traceparent(
+            "ff", TRACE_ID, SPAN_ID, "01"
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "0g", TRACE_ID, SPAN_ID, "01"
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "000", TRACE_ID, SPAN_ID, "01"
+        )));
+        assert!(!is_valid_traceparent(&format!(
+            "{}-extra",
+            traceparent("00", TRACE_ID, SPAN_ID, "01")
+        )));
+    }
+
+    #[test]
+    fn rejects_zero_ids() {
+        let zero_trace_id = "0".repeat(32);
+        let zero_span_id = "0".repeat(16);
+        assert!(!is_valid_traceparent(&traceparent(
+            "00",
+            &zero_trace_id,
+            SPAN_ID,
+            "01"
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "00",
+            TRACE_ID,
+            &zero_span_id,
+            "01"
+        )));
+    }
+
+    #[test]
+    fn rejects_malformed_traceparent() {
+        assert!(!is_valid_traceparent(""));
+        assert!(!is_valid_traceparent(&format!(
+            "00-{}-{}",
+            TRACE_ID, SPAN_ID
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "00",
+            &TRACE_ID.to_uppercase(),
+            SPAN_ID,
+            "01"
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "00",
+            &TRACE_ID[1..],
+            SPAN_ID,
+            "01"
+        )));
+        assert!(!is_valid_traceparent(&traceparent(
+            "00", TRACE_ID, SPAN_ID, "1"
+        )));
+    }
+
+    #[test]
+    fn extractor_ignores_invalid_traceparent() {
+        let mut headers = HeaderMap::new();
+        let header = traceparent("00", TRACE_ID, &"0".repeat(16), "01");
+        headers.insert(
+            HeaderName::from_static(TRACEPARENT),
+            HeaderValue::from_str(&header).unwrap(),
+        );
+        assert_eq!(HeaderExtractor(&headers).get(TRACEPARENT), None);
+
+        let header = traceparent("00", TRACE_ID, SPAN_ID, "01");
+        headers.insert(
+            HeaderName::from_static(TRACEPARENT),
+            HeaderValue::from_str(&header).unwrap(),
+        );
+        assert_eq!(
+            HeaderExtractor(&headers).get(TRACEPARENT),
+            Some(header.as_str())
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Test support. Every test gets a throwaway database created from a template,
+// which is migrated once per set of migrations, so tests are isolated from each other
+// and don't pay for migrations.
+//
+// Settings of the test env are passed by `rrd test api`, which layers and expands env files,
+// so tests aren't run with plain `cargo test`.
+//
+// Usage:
+//   #[actix_rt::test]
+//   async fn test() {
+//       let api = TestApi::new().await;
+//       let res = api.execute("{ posts { id } }", json!({})).await;
+//       assert_eq!(testing::data(&res), json!({ "posts": [] }));
+//   }
+
+use std::{sync::Once, thread};
+
+use actix_web::web;
+use futures::lock::Mutex;
+use graphql::{Request, Response, Variables};
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgConnection, Connection, Executor, PgPool};
+
+use crate::{
+    env,
+    gql::{self, schema::GqlSchema},
+    pg::migrations,
+};
+
+lazy_static! {
+    static ref TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+static ENV: Once = Once::new();
+
+pub struct TestDb {
+    pub pool: PgPool,
+    name: String,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        check_env();
+        let template = template().await;
+        let name = format!("{}_{:016x}", env::pg_database(), rand::random::<u64>());
+        admin()
+            .await
+            .execute(format!(r#"CREATE DATABASE "{}" TEMPLATE "{}""#, name, template).as_str())
+            .await
+            .expect("Failed to create test database");
+        let pool = PgPool::connect(&url(&name))
+            .await
+            .expect("Failed to connect to test database");
+        Self { pool, name }
+    }
+}
+
+// Drop can't be async and the runtime of the test is blocked at this point,
+// so the database is dropped from a separate thread with its own runtime.
+// It happens on panics too, so failed tests don't leave databases behind.
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let name = self.name.clone();
+        let res = thread::spawn(move || {
+            actix_rt::System::new("test-db").block_on(async move {
+                admin()
+                    .await
+                    .execute(format!(r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#, name).as_str())
+                    .await
+            })
+        })
+        .join();
+        if let Ok(Err(error)) = res {
+            eprintln!("Failed to drop test database {}: {}", self.name, error);
+        }
+    }
+}
+
+pub struct TestApi {
+    pub db: TestDb,
+    schema: GqlSchema,
+}
+
+impl TestApi {
+    pub async fn new() -> Self {
+        Self {
+            db: TestDb::new().await,
+            schema: gql::schema::new(),
+        }
+    }
+
+    pub async fn execute(&self, query: &str, variables: serde_json::Value) -> Response {
+        let req = Request::new(query)
+            .variables(Variables::from_json(variables))
+            .data(web::Data::new(self.db.pool.clone()));
+        self.schema.execute(req).await
+    }
+}
+
+// Data of a successful response, panics with the errors otherwise
+pub fn data(res: &Response) -> serde_json::Value {
+    if !res.errors.is_empty() {
+        panic!(
+            "Expected successful response, got errors: {:?}",
+            reasons(res)
+        );
+    }
+    serde_json::to_value(&res.data).expect("Failed to serialize response data")
+}
+
+// Reasons as they're reported to the client, e.g. `POST_NOT_FOUND`
+pub fn reasons(res: &Response) -> Vec<String> {
+    res.errors.iter().map(gql::reason).collect()
+}
+
+fn check_env() {
+    ENV.call_once(|| {
+        if std::env::var("PG_DATABASE").is_err() {
+            panic!("Settings of the test env are not set. Run API tests with `rrd test api`.");
+        }
+    })
+}
+
+fn url(db: &str) -> String {
+    format!(
+        "postgres://{user}:{password}@{host}:{port}/{db}",
+        user = env::pg_user(),
+        password = env::pg_password(),
+        host = env::pg_host(),
+        port = env::pg_port(),
+        db = db
+    )
+}
+
+async fn admin() -> PgConnection {
+    PgConnection::connect(&url("postgres"))
+        .await
+        .expect("Failed to connect to postgres database")
+}
+
+// Template name is derived from migrations, so changing them results in a fresh template
+async fn template() -> String {
+    let mut template = TEMPLATE.lock().await;
+    if let Some(name) = template.as_ref() {
+        return name.clone();
+    }
+
+    let migrations = migrations::embedded().expect("Failed to load migrations");
+    let mut hasher = Sha256::new();
+    for migration in &migrations {
+        hasher.update(migration.checksum());
+    }
+    let name = format!(
+        "{}_template_{}",
+        env::pg_database(),
+        &format!("{:x}", hasher.finalize())[..12]
+    );
+
+    let mut conn = admin().await;
+    let exists: Option<(i32,)> = sqlx::query_as("SELECT 1 FROM pg_database WHERE datname = $1")
+        .bind(&name)
+        .fetch_optional(&mut conn)
+        .await
+        .expect("Failed to look up template database");
+
+    if exists.is_none() {
+        // Migrated under a temporary name, so an interrupted run doesn't leave a broken template
+        let tmp = format!("{}_{:08x}", name, rand::random::<u32>());
+        conn.execute(format!(r#"CREATE DATABASE "{}""#, tmp).as_str())
+            .await
+            .expect("Failed to create template database");
+        let pool = PgPool::connect(&url(&tmp))
+            .await
+            .expect("Failed to connect to template database");
+        migrations::run(&pool, &migrations)
+            .await
+            .unwrap_or_else(|error| panic!("Failed to migrate template database: {}", error));
+        pool.close().await;
+        conn.execute(format!(r#"ALTER DATABASE "{}" RENAME TO "{}""#, tmp, name).as_str())
+            .await
+            .expect("Failed to rename template database");
+    }
+
+    *template = Some(name.clone());
+    name
+}
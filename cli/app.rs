@@ -1,6 +1,15 @@
-use clap::clap_app;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    path::Path,
+};
 
-use crate::{error, services::*, Env, Error, Exec, Result, TcpAddr, CFG};
+use clap::{clap_app, Arg, ArgGroup};
+
+use crate::{
+    e2e, error, guard, printer, schema, secrets, services::*, spec, Dir, Env, Error, Exec, File,
+    Result, CFG,
+};
 
 pub struct App(clap::App<'static>);
 
@@ -13,13 +22,65 @@ impl App {
             (@setting ArgRequiredElseHelp)
             (@subcommand setup => (about: "Sets up environment incl. database, dependencies, etc."))
             (@subcommand update => (about: "Updates environment incl. database, dependencies, etc."))
-            (@subcommand reset => (about: "Resets environment incl. database, dependencies, etc."))
+            (@subcommand reset =>
+              (about: "Resets environment incl. database, dependencies, etc.")
+              (arg: App::confirm_arg())
+            )
             (@subcommand develop =>
               (visible_aliases: &["dev"])
               (about: "Runs the app incl. api server, web client etc")
-              (@arg prod: -p --production "Runs a production build")
+              (args: App::env_args())
+              (group: App::env_group().required(false).multiple(false))
               (@arg "rescript-log-level": --"rescript-log-level" +takes_value "Sets log level for ReScript app")
             )
+            (@subcommand test =>
+              (about: "Runs tests")
+              (arg: App::test_target_arg())
+            )
+            (@subcommand e2e =>
+              (about: "Runs end-to-end scenarios against an isolated stack on random ports")
+              (@arg scenarios: -s --scenarios +takes_value "Scenarios file [default: e2e/scenarios.yml]")
+            )
+            (@subcommand env =>
+                (about: "Environment configuration commands")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand check =>
+                  (about: "Validates env files against the declared schema and reports every problem")
+                  (@arg env: -e --env +takes_value "Environment to check [default: all]")
+                )
+                (@subcommand show =>
+                  (about: "Prints effective values of an environment with secrets masked")
+                  (arg: App::env_arg())
+                )
+                (@subcommand diff =>
+                  (about: "Prints values that differ between two environments")
+                  (@arg a: +required "Environment")
+                  (@arg b: +required "Environment to compare with")
+                )
+            )
+            (@subcommand secrets =>
+                (about: "Encrypted secrets commands")
+                (@setting ArgRequiredElseHelp)
+                (@subcommand edit =>
+                  (about: "Opens decrypted secrets in $EDITOR and encrypts them on save")
+                  (arg: App::env_arg())
+                )
+                (@subcommand set =>
+                  (about: "Sets a secret")
+                  (arg: App::env_arg())
+                  (@arg name: +required "Name of the secret, e.g. PG_PASSWORD")
+                  (@arg value: "Value of the secret, prompted for when omitted")
+                )
+                (@subcommand get =>
+                  (about: "Prints a secret")
+                  (arg: App::env_arg())
+                  (@arg name: +required "Name of the secret")
+                )
+                (@subcommand rotate =>
+                  (about: "Re-encrypts secrets with a new key")
+                  (arg: App::env_arg())
+                )
+            )
             (@subcommand api =>
                 (about: "API server commands")
                 (@setting ArgRequiredElseHelp)
@@ -28,11 +89,19 @@ impl App {
                   (@arg release: -r --release "Builds release")
                 )
                 (@subcommand clean => (about: "Cleans API artefacts"))
+                (@subcommand cert => (about: "Generates self-signed TLS certificate for local development"))
+                (@subcommand schema =>
+                    (about: "GraphQL schema commands")
+                    (@setting ArgRequiredElseHelp)
+                    (@subcommand sdl => (about: "Prints GraphQL schema in SDL"))
+                    (@subcommand write => (about: "Writes GraphQL schema used by ReScript app"))
+                    (@subcommand check => (about: "Fails if GraphQL schema has breaking changes compared to the committed one"))
+                )
                 (@subcommand run =>
                     (about: "Runs API server")
-                    (@group env =>
-                      (@arg release: -r --release "Runs release build")
-                    )
+                    (@arg release: -r --release "Runs release build against production environment, unless --env is set")
+                    (args: App::env_args())
+                    (group: App::env_group().required(false).multiple(false))
                     (@arg watch: -w --watch "Recompiles an API server on a source change")
                 )
             )
@@ -57,13 +126,8 @@ impl App {
                   (visible_aliases: &["c"])
                 )
                 (@subcommand graphql =>
-                  (about: "Writes GraphQL schema used by ReScript app")
+                  (about: "Writes GraphQL schema used by ReScript app, same as `rrd api schema write`")
                   (visible_aliases: &["gql"])
-                  (@group env =>
-                      (@arg dev: -d --development "Uses development API server [default]")
-                      (@arg prod: -p --production "Uses production API server")
-                      (@arg test: -t --test "Uses test API server")
-                  )
                 )
             )
             (@subcommand db =>
@@ -71,41 +135,48 @@ impl App {
                 (@setting ArgRequiredElseHelp)
                 (@subcommand create =>
                   (about: "Creates database")
-                  (@group env =>
-                      (@attributes ... +required)
-                      (@arg dev: -d --development "Creates development database")
-                      (@arg prod: -p --production "Creates production database")
-                      (@arg test: -t --test "Creates test database")
-                  )
+                  (args: App::env_args())
+                  (group: App::env_group())
                 )
                 (@subcommand drop =>
                   (about: "Drops database")
-                  (@group env =>
-                      (@attributes ... +required)
-                      (@arg dev: -d --development "Drops development database")
-                      (@arg prod: -p --production "Drops production database")
-                      (@arg test: -t --test "Drops test database")
-                  )
+                  (arg: App::confirm_arg())
+                  (args: App::env_args())
+                  (group: App::env_group())
                 )
                 (@subcommand reset =>
                   (about: "Resets Postgres datbases")
+                  (arg: App::confirm_arg())
                   (@arg prepare: --prepare "Prepares database schema")
-                  (@group env =>
-                      (@attributes ... +required)
-                      (@arg dev: -d --development "Resets development database")
-                      (@arg prod: -p --production "Resets production database")
-                      (@arg test: -t --test "Resets test database")
-                  )
+                  (@arg seed: --seed "Seeds databases with fake data")
+                  (args: App::env_args())
+                  (group: App::env_group())
                 )
                 (@subcommand schema =>
                   (about: "Prepares database schema")
-                  (@group env =>
-                      (@attributes ... +required)
-                      (@arg dev: -d --development "Prepares schema against development database")
-                      (@arg prod: -p --production "Prepares schema against production database")
-                      (@arg test: -t --test "Prepares schema against test database")
-                  )
+                  (args: App::env_args())
+                  (group: App::env_group())
+                )
+                (@subcommand seed =>
+                  (about: "Seeds database with deterministic fake data")
+                  (arg: App::confirm_arg())
+                  (arg: App::env_arg())
+                  (@arg count: -c --count +takes_value "Number of posts [default: 20]")
+                  (@arg seed: -s --seed +takes_value "Seed of random generator [default: 42]")
                 )
+                (@subcommand dump =>
+                  (about: "Dumps database into a file")
+                  (arg: App::env_arg())
+                  (@arg out: -o --out +takes_value "Output file [default: backups/<database>-<timestamp>.dump]")
+                )
+                (@subcommand restore =>
+                  (about: "Restores database from a dump")
+                  (arg: App::confirm_arg())
+                  (arg: App::env_arg())
+                  (@arg file: +required "Dump file")
+                )
+                (subcommand: App::export_posts())
+                (subcommand: App::import_posts())
                 (@subcommand migrations =>
                     (about: "Postgres migration commands")
                     (visible_aliases: &["mg", "mig"])
@@ -116,12 +187,18 @@ impl App {
                     )
                     (@subcommand run =>
                       (about: "Runs migrations")
-                      (@group env =>
-                          (@attributes ... +required)
-                          (@arg dev: -d --development "Runs migrations against development database")
-                          (@arg prod: -p --production "Runs migrations against production database")
-                          (@arg test: -t --test "Runs migrations against test database")
-                      )
+                      (args: App::env_args())
+                      (group: App::env_group())
+                    )
+                    (@subcommand revert =>
+                      (about: "Reverts the latest migration")
+                      (arg: App::confirm_arg())
+                      (arg: App::env_arg())
+                      (@arg to: --to +takes_value "Reverts every migration applied after this version, 0 reverts all")
+                    )
+                    (@subcommand status =>
+                      (about: "Prints applied, pending and checksum-mismatched migrations")
+                      (@arg env: -e --env +takes_value "Environment: development, production, test or any other from env/ dir [default: all]")
                     )
                 )
             )
@@ -156,14 +233,15 @@ impl App {
                 Exec::cmd(yarn::install()).await?;
 
                 // Setting up Postgres
+                let envs = CFG.envs_with_unique_dbs();
                 let mut cmds = vec![];
-                for env in CFG.envs_with_unique_dbs() {
+                for env in &envs {
                     cmds.extend(vec![
-                        postgres::create_database(&env),
-                        postgres::run_migrations(&env),
+                        postgres::create_database(env),
+                        postgres::run_migrations(env),
                     ])
                 }
-                postgres::run_one_off_cmds_against_db(cmds).await
+                postgres::run_one_off_cmds_against_db(&envs, cmds).await
             }
             Some(("update", _)) => {
                 // Updating CLI
@@ -180,13 +258,25 @@ impl App {
                 Exec::cmd(yarn::install()).await?;
 
                 // Running migrations
+                let envs = CFG.envs_with_unique_dbs();
                 let mut cmds = vec![];
-                for env in CFG.envs_with_unique_dbs() {
-                    cmds.extend(vec![postgres::run_migrations(&env)])
+                for env in &envs {
+                    cmds.extend(vec![postgres::run_migrations(env)])
                 }
-                postgres::run_one_off_cmds_against_db(cmds).await
+                postgres::run_one_off_cmds_against_db(&envs, cmds).await
             }
-            Some(("reset", _)) => {
+            Some(("reset", args)) => {
+                // Confirming destructive commands before anything gets reset
+                let envs = CFG.envs_with_unique_dbs();
+                let mut guards = vec![];
+                for env in &envs {
+                    guards.push(guard::destructive(
+                        env,
+                        "reset",
+                        args.is_present("yes-i-mean-it"),
+                    )?);
+                }
+
                 // Checking system and environment
                 sys::ensure_prerequisites().await?;
 
@@ -201,14 +291,15 @@ impl App {
 
                 // Resetting Postgres
                 let mut cmds = vec![];
-                for env in CFG.envs_with_unique_dbs() {
+                for (env, guard) in envs.iter().zip(guards) {
+                    cmds.extend(guard);
                     cmds.extend(vec![
-                        postgres::drop_database(&env),
-                        postgres::create_database(&env),
-                        postgres::run_migrations(&env),
+                        postgres::drop_database(env),
+                        postgres::create_database(env),
+                        postgres::run_migrations(env),
                     ])
                 }
-                postgres::run_one_off_cmds_against_db(cmds).await
+                postgres::run_one_off_cmds_against_db(&envs, cmds).await
             }
             Some(("develop", args)) => {
                 let rescript_log_level = match args.value_of("rescript-log-level") {
@@ -221,24 +312,158 @@ impl App {
                 ))
                 .await?;
 
-                if args.is_present("prod") {
-                    Exec::process_pool(vec![
-                        docker::compose::up(),
-                        api::watch_release(),
-                        client::rescript::watch(Some(rescript_log_level)),
-                        client::webpack::serve(&Env::Prod),
-                    ])
-                    .await
+                let env = App::env_from_args(args)?.unwrap_or_else(Env::dev);
+                CFG.check_pg(&env)?;
+                let mut processes = vec![];
+                // External database is managed elsewhere, so Docker services aren't needed
+                if CFG.pg_external(&env) {
+                    postgres::ensure_pg(&[env.clone()]).await?;
                 } else {
-                    Exec::process_pool(vec![
-                        docker::compose::up(),
-                        api::watch_dev(),
-                        client::rescript::watch(Some(rescript_log_level)),
-                        client::webpack::serve(&Env::Dev),
-                    ])
-                    .await
+                    processes.push(docker::compose::up());
                 }
+                processes.extend(vec![
+                    api::watch(&env, env.is_prod()),
+                    client::rescript::watch(Some(rescript_log_level)),
+                    client::webpack::serve(&env),
+                ]);
+                Exec::process_pool(processes).await
             }
+            Some(("test", args)) => match args.value_of("target") {
+                Some("api") => {
+                    postgres::run_one_off_cmds_against_db(&[Env::test()], vec![api::test()]).await
+                }
+                Some("client") => Exec::cmd(client::rescript::make_world(None, false)).await,
+                Some(_) | None => {
+                    postgres::run_one_off_cmds_against_db(&[Env::test()], vec![api::test()])
+                        .await?;
+                    Exec::cmd(client::rescript::make_world(None, false)).await
+                }
+            },
+            Some(("e2e", args)) => {
+                let scenarios = match args.value_of("scenarios") {
+                    Some(scenarios) => App::absolute_path(scenarios)?,
+                    None => File::E2eScenarios.loc().display().to_string(),
+                };
+                e2e::run(Path::new(&scenarios)).await
+            }
+            Some(("env", env)) => match env.subcommand() {
+                Some(("check", args)) => {
+                    let envs = match args.value_of("env") {
+                        Some(_) => vec![App::env_from_value(args)?],
+                        None => CFG.envs(),
+                    };
+                    let mut problems = 0;
+                    for error in CFG.errors() {
+                        printer::print_warning(error);
+                    }
+                    for env in envs {
+                        let report = spec::check(CFG.data(&env));
+                        if !report.unknown.is_empty() {
+                            printer::print_warning(&format!(
+                                "Undeclared keys in {} env: {}",
+                                env,
+                                report.unknown.join(", ")
+                            ));
+                        }
+                        if report.errors.is_empty() {
+                            continue;
+                        }
+                        problems += report.errors.len();
+                        println!("\n{}", console::style(&env).bold());
+                        for error in &report.errors {
+                            println!("  {} {}", console::style("✗").red(), error);
+                        }
+                    }
+                    problems += CFG.errors().len();
+                    if problems > 0 {
+                        Err(
+                            error::other(format!("Found {} problem(s) in env files", problems))
+                                .into(),
+                        )
+                    } else {
+                        printer::print_info("Env files are valid");
+                        Ok(())
+                    }
+                }
+                Some(("show", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let data: BTreeMap<String, String> = CFG.env(&env).into_iter().collect();
+                    println!();
+                    for (key, value) in &data {
+                        println!("{}={}", key, CFG.masked(key, value));
+                    }
+                    Ok(())
+                }
+                Some(("diff", args)) => {
+                    let a = App::known_env(args.value_of("a").unwrap())?;
+                    let b = App::known_env(args.value_of("b").unwrap())?;
+                    let a_data: BTreeMap<String, String> = CFG.env(&a).into_iter().collect();
+                    let b_data: BTreeMap<String, String> = CFG.env(&b).into_iter().collect();
+                    let keys: BTreeSet<&String> = a_data.keys().chain(b_data.keys()).collect();
+                    let render = |key: &str, value: Option<&String>| match value {
+                        Some(value) => CFG.masked(key, value),
+                        None => "<unset>".to_string(),
+                    };
+
+                    let mut changes = 0;
+                    println!();
+                    for key in keys {
+                        let (a_value, b_value) = (a_data.get(key), b_data.get(key));
+                        if a_value == b_value {
+                            continue;
+                        }
+                        changes += 1;
+                        // Masked secrets look the same, so the fact they differ is stated explicitly
+                        let note = if CFG.is_secret(key) { " (differs)" } else { "" };
+                        println!(
+                            "{}  {} {} {}{}",
+                            console::style(key).bold(),
+                            console::style(render(key, a_value)).red(),
+                            console::style("→").dim(),
+                            console::style(render(key, b_value)).green(),
+                            note,
+                        );
+                    }
+                    if changes == 0 {
+                        printer::print_info(&format!("{} and {} envs are identical", a, b));
+                    }
+                    Ok(())
+                }
+                Some(_) | None => Err(Error::NothingToExecute),
+            },
+            Some(("secrets", secrets)) => match secrets.subcommand() {
+                Some(("edit", args)) => secrets::edit(&App::env_from_value(args)?).await,
+                Some(("set", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let name = args.value_of("name").unwrap();
+                    // Prompting keeps the value out of shell history
+                    let value = match args.value_of("value") {
+                        Some(value) => value.to_string(),
+                        None => {
+                            printer::print_info(&format!("Enter value of {}:", name));
+                            console::Term::stderr().read_secure_line()?
+                        }
+                    };
+                    secrets::set(&env, name, &value)?;
+                    printer::print_info(&format!("{} is set in {} secrets", name, env));
+                    Ok(())
+                }
+                Some(("get", args)) => {
+                    let env = App::env_from_value(args)?;
+                    println!("{}", secrets::get(&env, args.value_of("name").unwrap())?);
+                    Ok(())
+                }
+                Some(("rotate", args)) => {
+                    let env = App::env_from_value(args)?;
+                    secrets::rotate(&env)?;
+                    printer::print_info(&format!(
+                        "{} secrets are re-encrypted with a new key. Share it with the team out of band.",
+                        env
+                    ));
+                    Ok(())
+                }
+                Some(_) | None => Err(Error::NothingToExecute),
+            },
             Some(("api", api)) => match api.subcommand() {
                 Some(("build", args)) => {
                     if args.is_present("release") {
@@ -248,20 +473,64 @@ impl App {
                     }
                 }
                 Some(("clean", _)) => Exec::cmd(api::clean()).await,
-                Some(("run", args)) => {
-                    if args.is_present("release") {
-                        if args.is_present("watch") {
-                            Exec::process(api::watch_release()).await
-                        } else {
-                            Exec::process(api::run_release()).await
+                Some(("cert", _)) => {
+                    Exec::cmd(api::generate_dev_cert()).await?;
+                    printer::print_info(&format!(
+                        "Set API_TLS_CERT={} and API_TLS_KEY={} to serve HTTPS",
+                        File::DevTlsCert.relative_to(&Dir::Root),
+                        File::DevTlsKey.relative_to(&Dir::Root),
+                    ));
+                    Ok(())
+                }
+                Some(("schema", schema)) => match schema.subcommand() {
+                    Some(("sdl", _)) => Exec::cmd(api::print_schema_sdl()).await,
+                    Some(("write", _)) => {
+                        let next = Exec::output(api::print_schema_json()).await?;
+                        fs::write(File::GraphqlSchema.loc(), next)?;
+                        Ok(())
+                    }
+                    Some(("check", _)) => {
+                        let next = Exec::output(api::print_schema_json()).await?;
+                        let current = fs::read_to_string(File::GraphqlSchema.loc())?;
+                        let changes = schema::diff(&current, &next)?;
+                        if changes.is_empty() {
+                            printer::print_info("GraphQL schema is up to date");
+                            return Ok(());
                         }
-                    } else {
-                        if args.is_present("watch") {
-                            Exec::process(api::watch_dev()).await
+
+                        println!();
+                        for change in &changes {
+                            println!("{}", change);
+                        }
+                        let breaking = changes.iter().filter(|change| change.is_breaking()).count();
+                        if breaking > 0 {
+                            Err(error::other(format!(
+                                "GraphQL schema has {} breaking change(s)",
+                                breaking
+                            ))
+                            .into())
                         } else {
-                            Exec::process(api::run_dev()).await
+                            printer::print_info(
+                                "GraphQL schema has no breaking changes. Run `rrd api schema write` to update the committed one.",
+                            );
+                            Ok(())
                         }
                     }
+                    Some(_) | None => Err(Error::NothingToExecute),
+                },
+                Some(("run", args)) => {
+                    let release = args.is_present("release");
+                    let env = match App::env_from_args(args)? {
+                        Some(env) => env,
+                        None if release => Env::prod(),
+                        None => Env::dev(),
+                    };
+                    CFG.check_pg(&env)?;
+                    if args.is_present("watch") {
+                        Exec::process(api::watch(&env, release)).await
+                    } else {
+                        Exec::process(api::run(&env, release)).await
+                    }
                 }
                 Some(_) | None => Err(Error::NothingToExecute),
             },
@@ -281,82 +550,181 @@ impl App {
                     Exec::process(client::rescript::watch(log_level)).await
                 }
                 Some(("clean", _)) => Exec::cmd(client::rescript::clean_world()).await,
-                Some(("graphql", args)) => {
-                    let env = App::env_from_args(args).unwrap_or(Env::Dev);
-                    match CFG.api_health_url(&env).ping().await {
-                        Ok(()) => Exec::cmd(client::graphql::write_schema(&env)).await,
-                        Err(()) => {
-                            Exec::dependent_cmd(
-                                client::graphql::write_schema(&env),
-                                match env {
-                                    Env::Dev => api::run_dev(),
-                                    Env::Prod => api::run_release(),
-                                    Env::Test => unimplemented!(),
-                                },
-                                TcpAddr {
-                                    host: CFG.api_host(&env),
-                                    port: CFG.api_port(&env),
-                                },
-                            )
-                            .await
-                        }
-                    }
+                // Schema is generated by the API binary, so no server has to be running and
+                // introspection settings of the env don't matter
+                Some(("graphql", _)) => {
+                    let schema = Exec::output(api::print_schema_json()).await?;
+                    fs::write(File::GraphqlSchema.loc(), schema)?;
+                    Ok(())
                 }
                 Some(_) | None => Err(Error::NothingToExecute),
             },
             Some(("db", db)) => match db.subcommand() {
                 Some(("create", args)) => {
+                    let envs = App::envs_from_args(args)?;
                     let mut cmds = vec![];
-                    for env in App::envs_from_args(args) {
-                        cmds.extend(vec![postgres::create_database(&env)])
+                    for env in &envs {
+                        cmds.extend(vec![postgres::create_database(env)])
                     }
-                    postgres::run_one_off_cmds_against_db(cmds).await
+                    postgres::run_one_off_cmds_against_db(&envs, cmds).await
                 }
                 Some(("drop", args)) => {
+                    let envs = App::envs_from_args(args)?;
                     let mut cmds = vec![];
-                    for env in App::envs_from_args(args) {
-                        cmds.extend(vec![postgres::drop_database(&env)])
+                    for env in &envs {
+                        cmds.extend(guard::destructive(
+                            env,
+                            "drop",
+                            args.is_present("yes-i-mean-it"),
+                        )?);
+                        cmds.extend(vec![postgres::drop_database(env)])
                     }
-                    postgres::run_one_off_cmds_against_db(cmds).await
+                    postgres::run_one_off_cmds_against_db(&envs, cmds).await
                 }
                 Some(("schema", args)) => {
+                    let envs = App::envs_from_args(args)?;
                     let mut cmds = vec![];
-                    for env in App::envs_from_args(args) {
-                        cmds.extend(vec![postgres::prepare_database_schema(&env)])
+                    for env in &envs {
+                        cmds.extend(vec![postgres::prepare_database_schema(env)])
                     }
-                    postgres::run_one_off_cmds_against_db(cmds).await
+                    postgres::run_one_off_cmds_against_db(&envs, cmds).await
                 }
                 Some(("reset", args)) => {
-                    let envs = App::envs_from_args(args);
+                    let envs = App::envs_from_args(args)?;
                     let default_env = envs.first().unwrap().clone();
                     let mut cmds = vec![];
-                    for env in envs {
+                    for env in &envs {
+                        cmds.extend(guard::destructive(
+                            env,
+                            "reset",
+                            args.is_present("yes-i-mean-it"),
+                        )?);
                         cmds.extend(vec![
-                            postgres::drop_database(&env),
-                            postgres::create_database(&env),
-                            postgres::run_migrations(&env),
-                        ])
+                            postgres::drop_database(env),
+                            postgres::create_database(env),
+                            postgres::run_migrations(env),
+                        ]);
+                        if args.is_present("seed") {
+                            cmds.push(api::seed(env, None, None))
+                        }
                     }
 
                     if args.is_present("prepare") {
                         cmds.push(postgres::prepare_database_schema(&default_env));
                     }
 
-                    postgres::run_one_off_cmds_against_db(cmds).await
+                    postgres::run_one_off_cmds_against_db(&envs, cmds).await
+                }
+                Some(("seed", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let mut cmds =
+                        guard::destructive(&env, "seed", args.is_present("yes-i-mean-it"))?;
+                    cmds.push(api::seed(
+                        &env,
+                        args.value_of("count"),
+                        args.value_of("seed"),
+                    ));
+                    postgres::run_one_off_cmds_against_db(&[env], cmds).await
+                }
+                Some(("dump", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let out = match args.value_of("out") {
+                        Some(out) => App::absolute_path(out)?,
+                        None => postgres::dump_file(&env)?,
+                    };
+                    postgres::run_one_off_cmds_against_db(
+                        &[env.clone()],
+                        vec![postgres::dump_database(&env, &out)],
+                    )
+                    .await?;
+                    printer::print_info(&format!("Database is dumped to {}", out));
+                    Ok(())
+                }
+                Some(("restore", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let file = App::absolute_path(args.value_of("file").unwrap())?;
+                    let mut cmds =
+                        guard::destructive(&env, "restore", args.is_present("yes-i-mean-it"))?;
+                    cmds.push(postgres::restore_database(&env, &file));
+                    postgres::run_one_off_cmds_against_db(&[env], cmds).await
+                }
+                Some(("export-posts", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let out = args.value_of("out").map(App::absolute_path).transpose()?;
+                    postgres::run_one_off_cmds_against_db(
+                        &[env.clone()],
+                        vec![api::export_posts(
+                            &env,
+                            args.value_of("format").unwrap(),
+                            out,
+                        )],
+                    )
+                    .await
+                }
+                Some(("import-posts", args)) => {
+                    let env = App::env_from_value(args)?;
+                    let path = App::absolute_path(args.value_of("path").unwrap())?;
+                    let dry_run = args.is_present("dry-run");
+                    // Dry run only reads the database
+                    let mut cmds = if dry_run {
+                        vec![]
+                    } else {
+                        guard::destructive(
+                            &env,
+                            "import posts into",
+                            args.is_present("yes-i-mean-it"),
+                        )?
+                    };
+                    cmds.push(api::import_posts(
+                        &env,
+                        args.value_of("format").unwrap(),
+                        path,
+                        dry_run,
+                    ));
+                    postgres::run_one_off_cmds_against_db(&[env], cmds).await
                 }
                 Some(("migrations", migrations)) => match migrations.subcommand() {
                     Some(("new", migration)) => match migration.subcommand() {
                         Some((migration, _)) => {
-                            Exec::cmd(postgres::create_migration(migration.to_string())).await
+                            for file in postgres::create_migration(migration)? {
+                                printer::print_info(&format!("Created {}", file.display()));
+                            }
+                            Ok(())
                         }
                         None => Err(error::other("You must provide a migration name").into()),
                     },
                     Some(("run", args)) => {
+                        let envs = App::envs_from_args(args)?;
+                        let mut cmds = vec![];
+                        for env in &envs {
+                            cmds.extend(vec![postgres::run_migrations(env)])
+                        }
+                        postgres::run_one_off_cmds_against_db(&envs, cmds).await
+                    }
+                    Some(("revert", args)) => {
+                        let env = App::env_from_value(args)?;
+                        let to = args
+                            .value_of("to")
+                            .map(App::migration_version)
+                            .transpose()?;
+                        let mut cmds = guard::destructive(
+                            &env,
+                            "revert migrations of",
+                            args.is_present("yes-i-mean-it"),
+                        )?;
+                        cmds.push(postgres::revert_migrations(&env, to));
+                        postgres::run_one_off_cmds_against_db(&[env], cmds).await
+                    }
+                    Some(("status", args)) => {
+                        let envs = match args.value_of("env") {
+                            Some(_) => vec![App::env_from_value(args)?],
+                            None => CFG.envs_with_unique_dbs(),
+                        };
                         let mut cmds = vec![];
-                        for env in App::envs_from_args(args) {
-                            cmds.extend(vec![postgres::run_migrations(&env)])
+                        for env in &envs {
+                            cmds.push(postgres::migrations_status(env))
                         }
-                        postgres::run_one_off_cmds_against_db(cmds).await
+                        postgres::run_one_off_cmds_against_db(&envs, cmds).await
                     }
                     Some(_) | None => Err(Error::NothingToExecute),
                 },
@@ -371,29 +739,162 @@ impl App {
         }
     }
 
-    fn env_from_args(args: &clap::ArgMatches) -> Option<Env> {
-        if args.is_present("dev") {
-            Some(Env::Dev)
-        } else if args.is_present("prod") {
-            Some(Env::Prod)
-        } else if args.is_present("test") {
-            Some(Env::Test)
+    // `clap_app!` accepts only identifiers as subcommand names, so hyphenated ones are built here
+    fn export_posts() -> clap::App<'static> {
+        clap::App::new("export-posts")
+            .about("Exports posts")
+            .arg(App::env_arg())
+            .arg(App::posts_format_arg())
+            .arg(
+                Arg::new("out")
+                    .short('o')
+                    .long("out")
+                    .takes_value(true)
+                    .about("File for jsonl (stdout by default) or directory for markdown"),
+            )
+    }
+
+    fn import_posts() -> clap::App<'static> {
+        clap::App::new("import-posts")
+            .about("Imports posts: creates new ones and updates existing ones by id. Posts have no slugs, so slugs in files are ignored.")
+            .arg(App::confirm_arg())
+            .arg(App::env_arg())
+            .arg(App::posts_format_arg())
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .about("Prints what would change without writing anything"),
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .about("File for jsonl or directory for markdown"),
+            )
+    }
+
+    fn env_arg() -> Arg<'static> {
+        Arg::new("env")
+            .short('e')
+            .long("env")
+            .takes_value(true)
+            .required(true)
+            .about("Environment: development, production, test or any other from env/ dir")
+    }
+
+    fn confirm_arg() -> Arg<'static> {
+        Arg::new("yes-i-mean-it")
+            .long("yes-i-mean-it")
+            .about("Skips typed confirmation of destructive commands against protected database")
+    }
+
+    // Client has no tests yet, so building the ReScript app is what type-checks it
+    fn test_target_arg() -> Arg<'static> {
+        Arg::new("target")
+            .possible_values(&["api", "client", "all"])
+            .default_value("all")
+            .about("What to test")
+    }
+
+    fn posts_format_arg() -> Arg<'static> {
+        Arg::new("format")
+            .short('f')
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["jsonl", "markdown"])
+            .default_value("jsonl")
+            .about("Format of posts")
+    }
+
+    fn env_from_value(args: &clap::ArgMatches) -> std::result::Result<Env, Error> {
+        App::known_env(args.value_of("env").unwrap())
+    }
+
+    // Versions are passed to a shell command, so anything but a number is rejected
+    fn migration_version(value: &str) -> std::result::Result<i64, Error> {
+        value.parse().map_err(|_| {
+            error::invalid_input(format!(
+                "Invalid migration version: {}. Expected a number, e.g. 20201017112233.",
+                value
+            ))
+            .into()
+        })
+    }
+
+    // Commands are executed from the root dir, so paths relative to the cwd must be resolved first
+    fn absolute_path(path: &str) -> std::result::Result<String, Error> {
+        Ok(env::current_dir()?.join(path).display().to_string())
+    }
+
+    // `-d`, `-p` and `-t` are kept as aliases of `--env` for the built-in envs
+    fn env_args() -> Vec<Arg<'static>> {
+        vec![
+            Arg::new("env")
+                .short('e')
+                .long("env")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .about("Environment: development, production, test or any other from env/ dir"),
+            Arg::new("dev")
+                .short('d')
+                .long("development")
+                .about("Same as --env development"),
+            Arg::new("prod")
+                .short('p')
+                .long("production")
+                .about("Same as --env production"),
+            Arg::new("test")
+                .short('t')
+                .long("test")
+                .about("Same as --env test"),
+        ]
+    }
+
+    fn env_group() -> ArgGroup<'static> {
+        ArgGroup::new("envs")
+            .args(&["env", "dev", "prod", "test"])
+            .required(true)
+            .multiple(true)
+    }
+
+    fn known_env(name: &str) -> std::result::Result<Env, Error> {
+        let env = Env::new(name);
+        if CFG.has(&env) {
+            Ok(env)
         } else {
-            None
+            Err(error::invalid_input(format!(
+                "Unknown environment: {}. Available: {}",
+                name,
+                CFG.envs()
+                    .iter()
+                    .map(Env::to_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .into())
         }
     }
 
-    fn envs_from_args(args: &clap::ArgMatches) -> Vec<Env> {
+    fn env_from_args(args: &clap::ArgMatches) -> std::result::Result<Option<Env>, Error> {
+        Ok(App::envs_from_args(args)?.into_iter().next())
+    }
+
+    fn envs_from_args(args: &clap::ArgMatches) -> std::result::Result<Vec<Env>, Error> {
         let mut envs = vec![];
         if args.is_present("dev") {
-            envs.push(Env::Dev)
+            envs.push(Env::dev())
         }
         if args.is_present("prod") {
-            envs.push(Env::Prod)
+            envs.push(Env::prod())
         }
         if args.is_present("test") {
-            envs.push(Env::Test)
+            envs.push(Env::test())
+        }
+        for name in args.values_of("env").into_iter().flatten() {
+            let env = App::known_env(name)?;
+            if !envs.contains(&env) {
+                envs.push(env)
+            }
         }
-        envs
+        Ok(envs)
     }
 }
@@ -1,159 +1,273 @@
-use std::{collections::HashMap, iter::FromIterator};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+};
 
-use crate::{Env, EnvData, File, HttpAddr};
+use crate::{
+    envfile::{self, Layers},
+    error, secrets, spec, Env, EnvData, File, TcpAddr,
+};
 
 lazy_static! {
     pub static ref CFG: Cfg = Cfg::load();
 }
 
+// Connection settings of the Postgres instance run by Docker Compose, which is shared by envs
+// that don't set `PG_EXTERNAL=true`. Only `PG_DATABASE` can differ between those.
+const SHARED_PG_KEYS: &[&str] = &["PG_HOST", "PG_PORT", "PG_USER"];
+
 pub struct Cfg {
-    dev: EnvData,
-    prod: EnvData,
-    test: EnvData,
+    envs: BTreeMap<Env, EnvData>,
+    // Values of `.env` and `.env.local`, i.e. before env specific overrides
+    shared: EnvData,
+    // Envs that override shared Postgres settings without being marked as external
+    invalid_pg: BTreeMap<Env, String>,
+    // Envs whose secrets file can't be decrypted, their values would silently fall back to plain ones
+    invalid_secrets: BTreeMap<Env, String>,
+    // Keys that come from encrypted secrets files, they're masked on output
+    secret_keys: HashSet<String>,
+    // Missing and malformed files don't stop the CLI from loading, `rrd env check` reports them
+    errors: Vec<String>,
 }
 
 impl Cfg {
+    // Layers from the lowest to the highest: `.env`, `.env.local`, `.env.<env>`, `.env.<env>.local`
+    // and decrypted secrets. `.local` files are personal and git-ignored. Values are expanded
+    // after layering, see `envfile`.
     pub fn load() -> Self {
-        #[allow(deprecated)] // it was undeprecated
-        let base = HashMap::from_iter(
-            dotenv::from_path_iter(File::Env.loc())
-                .unwrap()
-                .map(Result::unwrap),
-        );
-        #[allow(deprecated)] // it was undeprecated
-        let dev_overrides = dotenv::from_path_iter(File::DevEnv.loc())
-            .unwrap()
-            .map(Result::unwrap);
-        #[allow(deprecated)] // it was undeprecated
-        let prod_overrides = dotenv::from_path_iter(File::ProdEnv.loc())
-            .unwrap()
-            .map(Result::unwrap);
-        #[allow(deprecated)] // it was undeprecated
-        let test_overrides = dotenv::from_path_iter(File::TestEnv.loc())
-            .unwrap()
-            .map(Result::unwrap);
-
-        // TODO: We don't want anything db related except db name to be env dependent
-        //       since we run only one instance of Postgres via Docker Compose.
-        //       So we should prevent overrides of PG_HOST, PG_PORT etc.
-
-        let mut dev = base.clone();
-        dev.extend(dev_overrides);
-        let mut prod = base.clone();
-        prod.extend(prod_overrides);
-        let mut test = base.clone();
-        test.extend(test_overrides);
+        let mut errors = vec![];
+        let base = Cfg::read(File::Env, true, &mut errors);
+        let local = Cfg::read(File::EnvLocal, false, &mut errors);
+
+        let mut shared = Layers::new();
+        shared.push(&base);
+        shared.push(&local);
+        // Expansion errors are reported per env below
+        let (shared, _) = shared.resolve();
+
+        let mut envs = BTreeMap::new();
+        let mut invalid_pg = BTreeMap::new();
+        let mut invalid_secrets = BTreeMap::new();
+        let mut secret_keys = HashSet::new();
+        for env in Env::builtin().into_iter().chain(Env::discover(".env.", "")) {
+            let mut layers = Layers::new();
+            layers.push(&base);
+            layers.push(&local);
+            layers.push(&Cfg::read(
+                File::EnvOverrides(env.clone()),
+                true,
+                &mut errors,
+            ));
+            layers.push(&Cfg::read(
+                File::EnvOverridesLocal(env.clone()),
+                false,
+                &mut errors,
+            ));
+            match secrets::load(&env) {
+                Ok(Some(secrets)) => {
+                    secret_keys.extend(secrets.keys().cloned());
+                    layers.push_literal(secrets);
+                }
+                Ok(None) => (),
+                Err(error) => {
+                    errors.push(error.to_string());
+                    invalid_secrets.insert(env.clone(), error.to_string());
+                }
+            }
+
+            let (data, failures) = layers.resolve();
+            for failure in failures {
+                errors.push(format!("{} env: {}", env, failure));
+            }
+
+            if data.get("PG_EXTERNAL").map(String::as_str) != Some("true") {
+                let overrides: Vec<String> = SHARED_PG_KEYS
+                    .iter()
+                    .filter(|key| data.get(**key) != shared.get(**key))
+                    .map(|key| key.to_string())
+                    .collect();
+                if !overrides.is_empty() {
+                    let msg = format!(
+                        "{} env overrides {} of the shared Postgres. Set PG_EXTERNAL=true in env/.env.{} if it uses its own server.",
+                        env,
+                        overrides.join(", "),
+                        env
+                    );
+                    errors.push(msg.clone());
+                    invalid_pg.insert(env.clone(), msg);
+                }
+            }
+
+            envs.insert(env, EnvData::new(data));
+        }
 
         Self {
-            dev: EnvData::new(dev),
-            prod: EnvData::new(prod),
-            test: EnvData::new(test),
+            envs,
+            shared: EnvData::new(shared),
+            invalid_pg,
+            invalid_secrets,
+            secret_keys,
+            errors,
         }
     }
 
-    pub fn data(&self, env: &Env) -> &EnvData {
-        match env {
-            Env::Dev => &self.dev,
-            Env::Prod => &self.prod,
-            Env::Test => &self.test,
+    // Lines that fail to parse are reported and skipped, the rest of the file is still used
+    fn read(file: File, required: bool, errors: &mut Vec<String>) -> Vec<(String, String)> {
+        let name = format!("env/{}", file.file_name());
+        if !file.exists() {
+            if required {
+                errors.push(format!(
+                    "{} is missing. Run `rrd setup` to create it.",
+                    name
+                ));
+            }
+            return vec![];
+        }
+        let text = match fs::read_to_string(file.loc()) {
+            Ok(text) => text,
+            Err(error) => {
+                errors.push(format!("Failed to read {}: {}", name, error));
+                return vec![];
+            }
+        };
+        let (entries, failures) = envfile::parse(&text);
+        for failure in failures {
+            errors.push(format!("Failed to parse {} at {}", name, failure));
         }
+        entries
     }
 
-    pub fn env(&self, env: &Env) -> EnvData {
-        self.data(env).to_owned()
+    pub fn errors(&self) -> &[String] {
+        &self.errors
     }
 
-    pub fn web_host(&self, env: &Env) -> String {
-        self.data(env)
-            .get("WEB_HOST")
-            .expect("Failed to get WEB_HOST")
-            .to_string()
+    pub fn is_secret(&self, key: &str) -> bool {
+        self.secret_keys.contains(key) || spec::is_secret(key)
     }
 
-    pub fn web_port(&self, env: &Env) -> String {
-        self.data(env)
-            .get("WEB_PORT")
-            .expect("Failed to get WEB_PORT")
-            .to_string()
+    pub fn masked(&self, key: &str, value: &str) -> String {
+        if self.is_secret(key) {
+            spec::mask(value)
+        } else {
+            value.to_string()
+        }
     }
 
-    pub fn api_host(&self, env: &Env) -> String {
-        self.data(env)
-            .get("API_HOST")
-            .expect("Failed to get API_HOST")
-            .to_string()
+    pub fn data(&self, env: &Env) -> &EnvData {
+        self.envs
+            .get(env)
+            .unwrap_or_else(|| panic!("Unknown environment: {}", env))
     }
 
-    pub fn api_port(&self, env: &Env) -> String {
-        self.data(env)
-            .get("API_PORT")
-            .expect("Failed to get API_PORT")
-            .to_string()
+    pub fn has(&self, env: &Env) -> bool {
+        self.envs.contains_key(env)
     }
 
-    pub fn api_graphql_path(&self, env: &Env) -> String {
-        self.data(env)
-            .get("API_GRAPHQL_PATH")
-            .expect("Failed to get API_GRAPHQL_PATH")
-            .to_string()
+    // Built-in envs go first, the rest are sorted by name
+    pub fn envs(&self) -> Vec<Env> {
+        let mut envs = Env::builtin();
+        for env in self.envs.keys() {
+            if !envs.contains(env) {
+                envs.push(env.clone())
+            }
+        }
+        envs
     }
 
-    pub fn api_health_path(&self, env: &Env) -> String {
-        self.data(env)
-            .get("API_HEALTH_PATH")
-            .expect("Failed to get API_HEALTH_PATH")
-            .to_string()
+    pub fn env(&self, env: &Env) -> EnvData {
+        self.data(env).to_owned()
     }
 
-    pub fn api_graphql_url(&self, env: &Env) -> HttpAddr {
-        HttpAddr {
-            host: self.api_host(env),
-            port: self.api_port(env),
-            path: self.api_graphql_path(env),
+    fn get(&self, env: &Env, key: &str) -> String {
+        match self.data(env).get(key) {
+            Some(value) => value.to_string(),
+            None => panic!(
+                "{} is not set in {} env. Run `rrd env check` for details.",
+                key, env
+            ),
         }
     }
 
-    pub fn api_health_url(&self, env: &Env) -> HttpAddr {
-        HttpAddr {
-            host: self.api_host(env),
-            port: self.api_port(env),
-            path: self.api_health_path(env),
-        }
+    pub fn web_host(&self, env: &Env) -> String {
+        self.get(env, "WEB_HOST")
+    }
+
+    pub fn web_port(&self, env: &Env) -> String {
+        self.get(env, "WEB_PORT")
+    }
+
+    pub fn api_graphql_path(&self, env: &Env) -> String {
+        self.get(env, "API_GRAPHQL_PATH")
+    }
+
+    pub fn api_health_path(&self, env: &Env) -> String {
+        self.get(env, "API_HEALTH_PATH")
     }
 
     pub fn pg_host(&self, env: &Env) -> String {
-        self.data(env)
-            .get("PG_HOST")
-            .expect("Failed to get PG_HOST")
-            .to_string()
+        self.get(env, "PG_HOST")
     }
 
     pub fn pg_port(&self, env: &Env) -> String {
-        self.data(env)
-            .get("PG_PORT")
-            .expect("Failed to get PG_PORT")
-            .to_string()
+        self.get(env, "PG_PORT")
     }
 
     pub fn pg_user(&self, env: &Env) -> String {
-        self.data(env)
-            .get("PG_USER")
-            .expect("Failed to get PG_USER")
-            .to_string()
+        self.get(env, "PG_USER")
     }
 
     pub fn pg_password(&self, env: &Env) -> String {
-        self.data(env)
-            .get("PG_PASSWORD")
-            .expect("Failed to get PG_PASSWORD")
-            .to_string()
+        self.get(env, "PG_PASSWORD")
     }
 
     pub fn pg_database(&self, env: &Env) -> String {
-        self.data(env)
-            .get("PG_DATABASE")
-            .expect("Failed to get PG_DATABASE")
-            .to_string()
+        self.get(env, "PG_DATABASE")
+    }
+
+    pub fn pg_external(&self, env: &Env) -> bool {
+        self.data(env).get("PG_EXTERNAL").map(String::as_str) == Some("true")
+    }
+
+    pub fn pg_protected(&self, env: &Env) -> bool {
+        self.data(env).get("PG_PROTECTED").map(String::as_str) == Some("true")
+    }
+
+    pub fn pg_addr(&self, env: &Env) -> TcpAddr {
+        TcpAddr {
+            host: self.pg_host(env),
+            port: self.pg_port(env),
+        }
+    }
+
+    pub fn shared_pg_addr(&self) -> TcpAddr {
+        let get = |key| {
+            self.shared
+                .get(key)
+                .unwrap_or_else(|| panic!("{} is not set in env/.env", key))
+                .to_string()
+        };
+        TcpAddr {
+            host: get("PG_HOST"),
+            port: get("PG_PORT"),
+        }
+    }
+
+    // Commands against the database of an env must not run when the shared Postgres
+    // settings are overridden by accident: they'd silently target another server
+    pub fn check_pg(&self, env: &Env) -> io::Result<()> {
+        self.check_secrets(env)?;
+        match self.invalid_pg.get(env) {
+            Some(msg) => Err(error::invalid_input(msg)),
+            None => Ok(()),
+        }
+    }
+
+    // Without the key, secret values such as `PG_PASSWORD` would fall back to the plain env files
+    pub fn check_secrets(&self, env: &Env) -> io::Result<()> {
+        match self.invalid_secrets.get(env) {
+            Some(msg) => Err(error::invalid_input(msg)),
+            None => Ok(()),
+        }
     }
 
     pub fn pg_url(&self, env: &Env) -> String {
@@ -167,16 +281,24 @@ impl Cfg {
         )
     }
 
+    // Envs sharing a database with a preceding env are skipped, so the database is handled once.
+    // External databases are managed elsewhere, they're only touched when an env is given explicitly.
     pub fn envs_with_unique_dbs(&self) -> Vec<Env> {
-        let dev_db = self.pg_database(&Env::Dev);
-        let prod_db = self.pg_database(&Env::Prod);
-        let test_db = self.pg_database(&Env::Test);
-        let mut envs = vec![Env::Dev];
-        if prod_db != dev_db {
-            envs.push(Env::Prod)
-        }
-        if test_db != dev_db && test_db != prod_db {
-            envs.push(Env::Test)
+        let mut dbs = vec![];
+        let mut envs = vec![];
+        for env in self.envs() {
+            if self.pg_external(&env) {
+                continue;
+            }
+            let db = (
+                self.pg_host(&env),
+                self.pg_port(&env),
+                self.pg_database(&env),
+            );
+            if !dbs.contains(&db) {
+                dbs.push(db);
+                envs.push(env);
+            }
         }
         envs
     }
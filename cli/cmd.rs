@@ -2,7 +2,7 @@ use std::{
     io,
     process::Stdio,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
+        atomic::{AtomicUsize, Ordering},
         Arc,
     },
     time::{Duration, Instant},
@@ -12,20 +12,20 @@ use console::Color;
 use nix::{errno::Errno, sys::signal::Signal, unistd::Pid, Error as NixError};
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
-    process::{Child, Command},
+    process::Command,
     signal,
     sync::Mutex,
     task, time,
 };
 
-use crate::{error, printer, Dir, EnvData, Error, Result, TcpAddr};
+use crate::{error, printer, Dir, EnvData, Error, Result};
 
 #[derive(Clone)]
 pub struct Cmd {
     pub run: String,
     pub env: EnvData,
     pub dir: Dir,
-    pub msg: &'static str,
+    pub msg: String,
 }
 
 impl Cmd {
@@ -44,31 +44,27 @@ impl Cmd {
     pub fn shelled(cmd: &str) -> Vec<&str> {
         vec!["/c", &cmd]
     }
-}
 
-enum ProcessStatus {
-    Ready,
-    Running(Arc<Mutex<Child>>),
-}
+    // Quotes an argument interpolated into `run`, so the shell passes it through as is
+    #[cfg(unix)]
+    pub fn quote(arg: &str) -> String {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
 
-pub enum ProcessOutput {
-    Visible,
-    // Hidden,
+    #[cfg(windows)]
+    pub fn quote(arg: &str) -> String {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    }
 }
 
 pub struct Process {
     tag: &'static str,
     cmd: Cmd,
-    status: ProcessStatus,
 }
 
 impl Process {
     pub fn new(tag: &'static str, cmd: Cmd) -> Self {
-        Process {
-            tag,
-            cmd,
-            status: ProcessStatus::Ready,
-        }
+        Process { tag, cmd }
     }
 
     pub fn timeout() -> Duration {
@@ -82,103 +78,6 @@ impl Process {
     pub fn cmd(&self) -> &Cmd {
         &self.cmd
     }
-
-    pub async fn up(&mut self, output: ProcessOutput) {
-        let cmd = &self.cmd;
-
-        println!(
-            "❯ {} {}",
-            console::style(format!("{}...", cmd.msg)).bold(),
-            console::style(format!("$ {} @ {}", cmd.run, cmd.dir.display())).dim()
-        );
-
-        let stdio = match output {
-            ProcessOutput::Visible => Stdio::inherit,
-            // ProcessOutput::Hidden => Stdio::null,
-        };
-
-        let child = Command::new(Cmd::SHELL)
-            .args(Cmd::shelled(&cmd.run))
-            .envs(cmd.env.to_owned())
-            .current_dir(cmd.dir.loc())
-            .stdout(stdio())
-            .stderr(stdio())
-            .spawn()
-            .expect(&format!("Failed to spawn the process: {}", cmd.run));
-
-        self.status = ProcessStatus::Running(Arc::new(Mutex::new(child)));
-    }
-
-    #[cfg(unix)]
-    pub async fn down(&mut self) -> io::Result<()> {
-        match self.status {
-            ProcessStatus::Ready => Err(error::invalid_input("Process is not running")),
-            ProcessStatus::Running(ref process) => {
-                let mut process = process.lock().await;
-                match process.id() {
-                    Some(process_id) => {
-                        let pid = Pid::from_raw(process_id as i32);
-                        match nix::sys::signal::kill(pid, Signal::SIGINT) {
-                            Ok(()) => {
-                                let res = tokio::select! {
-                                    res = process.wait() => Some(res),
-                                    _ = time::sleep(Process::timeout()) => None,
-                                };
-                                match res {
-                                    Some(Ok(_)) => Ok(()),
-                                    Some(Err(error)) => {
-                                        println!("IO error on SIGINT: {}", error);
-                                        self.kill().await
-                                    }
-                                    None => {
-                                        println!("Killing process after timeout");
-                                        self.kill().await
-                                    }
-                                }
-                            }
-                            Err(NixError::Sys(Errno::EINVAL)) => {
-                                println!("Invalid signal. Killing process {}", pid);
-                                self.kill().await
-                            }
-                            Err(NixError::Sys(Errno::EPERM)) => Err(error::invalid_input(format!(
-                                "Insufficient permissions to signal process {}",
-                                pid
-                            ))),
-                            Err(NixError::Sys(Errno::ESRCH)) => Err(error::invalid_input(format!(
-                                "Process {} does not exist",
-                                pid
-                            ))),
-                            Err(error) => {
-                                Err(error::invalid_input(format!("Unexpected error {}", error)))
-                            }
-                        }
-                    }
-                    None => Err(error::invalid_input("Process {} does not have an id")),
-                }
-            }
-        }
-    }
-
-    #[cfg(windows)]
-    pub async fn down(&mut self) -> io::Result<()> {
-        match self.status {
-            ProcessStatus::Stopped => Err(error::invalid_input("Process is not running")),
-            ProcessStatus::Running(process) => self.kill(),
-        }
-    }
-
-    pub async fn kill(&self) -> io::Result<()> {
-        match self.status {
-            ProcessStatus::Ready => Ok(()),
-            ProcessStatus::Running(ref process) => {
-                let mut process = process.lock().await;
-                if let Ok(()) = process.kill().await {
-                    process.wait().await?;
-                }
-                Ok(())
-            }
-        }
-    }
 }
 
 pub struct Exec;
@@ -211,6 +110,32 @@ impl Exec {
             })
     }
 
+    // Same as `cmd`, but stdout is captured and returned instead of being printed
+    pub async fn output(cmd: Cmd) -> std::result::Result<String, Error> {
+        println!(
+            "❯ {} {}",
+            console::style(format!("{}:", cmd.msg)).bold(),
+            console::style(format!("$ {} @ {}", cmd.run, cmd.dir.display())).dim()
+        );
+
+        let res = Command::new(Cmd::SHELL)
+            .args(Cmd::shelled(&cmd.run))
+            .envs(cmd.env.to_owned())
+            .current_dir(cmd.dir.loc())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect(&format!("Failed to spawn the process: {}", cmd.run))
+            .wait_with_output()
+            .await?;
+
+        if res.status.success() {
+            String::from_utf8(res.stdout).map_err(|error| error::other(error).into())
+        } else {
+            Err(Error::Piped(res.status))
+        }
+    }
+
     pub async fn process(x: Process) -> Result {
         let cmd = x.cmd();
 
@@ -248,34 +173,20 @@ impl Exec {
         Ok(())
     }
 
-    pub async fn dependent_cmd(cmd: Cmd, mut process: Process, addr: TcpAddr) -> Result {
-        let cmd_done = Arc::new(AtomicBool::new(false));
-        let process_exited = Arc::new(AtomicBool::new(false));
-
-        task::spawn({
-            let cmd_done = cmd_done.clone();
-            let process_exited = process_exited.clone();
-
-            async move {
-                process.up(ProcessOutput::Visible).await;
-                while !cmd_done.load(Ordering::Relaxed) {
-                    time::sleep(Duration::from_millis(500)).await;
-                }
-                process.down().await.unwrap();
-                process_exited.store(true, Ordering::Relaxed);
-            }
-        });
-
-        addr.wait().await?;
-        Exec::cmd(cmd).await?;
-        cmd_done.store(true, Ordering::Relaxed);
-        while !process_exited.load(Ordering::Relaxed) {}
+    pub async fn process_pool(pool: Vec<Process>) -> Result {
+        let pool = Exec::spawn_pool(pool, PoolOutput::Visible);
+        signal::ctrl_c().await.unwrap();
+        pool.stop().await;
         Ok(())
     }
 
-    pub async fn process_pool(pool: Vec<Process>) -> Result {
+    // Each process of the pool runs in its own process group, so the pool decides when
+    // and how its processes are stopped, incl. everything they've spawned (e.g. `cargo run`)
+    pub fn spawn_pool(pool: Vec<Process>, output: PoolOutput) -> RunningPool {
         let pool_size = pool.len();
         let exited_processes = Arc::new(AtomicUsize::new(0));
+        let mut pids = vec![];
+        let mut logs = vec![];
 
         let tag_col_length = pool.iter().fold(0, |acc, process| {
             let len = process.tag().len();
@@ -308,77 +219,91 @@ impl Exec {
 
         for (process, color) in processes {
             let exited_processes = exited_processes.clone();
+            let cmd = process.cmd();
+            let tag = process.tag().to_string();
+            let colored_tag = console::style(tag.to_owned()).fg(color).bold();
+            let colored_tag_col = {
+                let len = tag.len();
+                let pad = " ".repeat(if len < tag_col_length {
+                    tag_col_length - len + 2
+                } else {
+                    2
+                });
+                console::style(format!(
+                    "{tag}{pad}{pipe}",
+                    tag = colored_tag,
+                    pad = pad,
+                    pipe = console::style("|").fg(color).bold()
+                ))
+            };
+
+            println!(
+                "{tag} ❯ {msg} {cmd}",
+                tag = colored_tag_col,
+                msg = console::style(format!("{}...", cmd.msg)).bold(),
+                cmd = console::style(format!("$ {} @ {}", cmd.run, cmd.dir.display())).dim()
+            );
+
+            // Processes run in background process groups, where reading from the terminal
+            // would stop them with SIGTTIN, so none of them gets stdin
+            let mut child = Command::from(Exec::group_leader(cmd))
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .expect(&format!("Failed to spawn the process: {}", cmd.run));
+
+            if let Some(pid) = child.id() {
+                pids.push(pid);
+            }
 
-            task::spawn(async move {
-                let cmd = &process.cmd();
-                let tag = process.tag().to_string();
-                let colored_tag = console::style(tag.to_owned()).fg(color).bold();
-                let colored_tag_col = {
-                    let len = tag.len();
-                    let pad = " ".repeat(if len < tag_col_length {
-                        tag_col_length - len + 2
-                    } else {
-                        2
-                    });
-                    console::style(format!(
-                        "{tag}{pad}{pipe}",
-                        tag = colored_tag,
-                        pad = pad,
-                        pipe = console::style("|").fg(color).bold()
-                    ))
-                };
-
-                println!(
-                    "{tag} ❯ {msg} {cmd}",
-                    tag = colored_tag_col,
-                    msg = console::style(format!("{}...", cmd.msg)).bold(),
-                    cmd = console::style(format!("$ {} @ {}", cmd.run, cmd.dir.display())).dim()
-                );
-
-                let mut child = Command::new(Cmd::SHELL)
-                    .args(Cmd::shelled(&cmd.run))
-                    .envs(cmd.env.to_owned())
-                    .current_dir(cmd.dir.loc())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .expect(&format!("Failed to spawn the process: {}", cmd.run));
-
-                let child_stdout = child
-                    .stdout
-                    .take()
-                    .expect(&format!("Failed to get a handle to stdout of {}", cmd.run));
-
-                let child_stderr = child
-                    .stderr
-                    .take()
-                    .expect(&format!("Failed to get a handle to stderr of {}", cmd.run));
-
-                let mut child_stdout_reader = BufReader::new(child_stdout).lines();
-                let mut child_stderr_reader = BufReader::new(child_stderr).lines();
-
-                task::spawn({
-                    let tag = colored_tag_col.clone();
-                    async move {
-                        while let Some(line) = child_stdout_reader.next_line().await.unwrap() {
-                            println!("{} {}", tag, line);
+            let child_stdout = child
+                .stdout
+                .take()
+                .expect(&format!("Failed to get a handle to stdout of {}", cmd.run));
+
+            let child_stderr = child
+                .stderr
+                .take()
+                .expect(&format!("Failed to get a handle to stderr of {}", cmd.run));
+
+            let mut child_stdout_reader = BufReader::new(child_stdout).lines();
+            let mut child_stderr_reader = BufReader::new(child_stderr).lines();
+
+            let log = Arc::new(Mutex::new(ProcessLog {
+                tag: tag.to_owned(),
+                lines: vec![],
+            }));
+            logs.push(log.clone());
+
+            task::spawn({
+                let tag = colored_tag_col.clone();
+                let log = log.clone();
+                async move {
+                    while let Some(line) = child_stdout_reader.next_line().await.unwrap() {
+                        match output {
+                            PoolOutput::Visible => println!("{} {}", tag, line),
+                            PoolOutput::Captured => log.lock().await.lines.push(line),
                         }
                     }
-                });
+                }
+            });
 
-                task::spawn({
-                    let tag = colored_tag_col.clone();
-                    async move {
-                        while let Some(line) = child_stderr_reader.next_line().await.unwrap() {
-                            eprintln!("{} {}", tag, line);
+            task::spawn({
+                let tag = colored_tag_col.clone();
+                async move {
+                    while let Some(line) = child_stderr_reader.next_line().await.unwrap() {
+                        match output {
+                            PoolOutput::Visible => eprintln!("{} {}", tag, line),
+                            PoolOutput::Captured => log.lock().await.lines.push(line),
                         }
                     }
-                });
-
-                let res = child.wait_with_output().await;
+                }
+            });
 
-                match res {
-                    Ok(output) => match output.status.code() {
+            task::spawn(async move {
+                match child.wait().await {
+                    Ok(status) => match status.code() {
                         Some(0) => printer::print_info(&format!(
                             "Process {} exited with code 0.",
                             colored_tag
@@ -401,19 +326,125 @@ impl Exec {
             });
         }
 
-        signal::ctrl_c().await.unwrap();
+        RunningPool {
+            size: pool_size,
+            exited_processes,
+            pids,
+            logs,
+        }
+    }
+
+    #[cfg(unix)]
+    fn group_leader(cmd: &Cmd) -> std::process::Command {
+        use std::os::unix::process::CommandExt;
+
+        let mut command = std::process::Command::new(Cmd::SHELL);
+        command
+            .args(Cmd::shelled(&cmd.run))
+            .envs(cmd.env.to_owned())
+            .current_dir(cmd.dir.loc());
+        unsafe {
+            command.pre_exec(|| {
+                nix::unistd::setpgid(Pid::from_raw(0), Pid::from_raw(0))
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+            });
+        }
+        command
+    }
+
+    // There are no process groups on Windows, so `RunningPool::stop` kills process trees instead
+    #[cfg(windows)]
+    fn group_leader(cmd: &Cmd) -> std::process::Command {
+        let mut command = std::process::Command::new(Cmd::SHELL);
+        command
+            .args(Cmd::shelled(&cmd.run))
+            .envs(cmd.env.to_owned())
+            .current_dir(cmd.dir.loc());
+        command
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum PoolOutput {
+    // Output is printed as it comes, prefixed with a tag of the process
+    Visible,
+    // Output is kept in memory, so it can be reported once the pool is stopped
+    Captured,
+}
+
+#[derive(Clone)]
+pub struct ProcessLog {
+    pub tag: String,
+    pub lines: Vec<String>,
+}
+
+pub struct RunningPool {
+    size: usize,
+    exited_processes: Arc<AtomicUsize>,
+    pids: Vec<u32>,
+    logs: Vec<Arc<Mutex<ProcessLog>>>,
+}
+
+impl RunningPool {
+    pub fn has_exited_process(&self) -> bool {
+        self.exited_processes.load(Ordering::Relaxed) > 0
+    }
+
+    pub async fn logs(&self) -> Vec<ProcessLog> {
+        let mut logs = vec![];
+        for log in &self.logs {
+            logs.push(log.lock().await.clone());
+        }
+        logs
+    }
+
+    // Interrupts process groups first and kills the ones that didn't exit in time
+    #[cfg(unix)]
+    pub async fn stop(&self) {
+        self.signal(Signal::SIGINT);
 
         let expire = Instant::now() + Process::timeout();
-        while exited_processes.load(Ordering::Relaxed) < pool_size {
+        while self.exited_processes.load(Ordering::Relaxed) < self.size {
             if Instant::now() > expire {
-                printer::print_warning("Timeout. Exiting.");
-                // TODO: Send SIGKILL to all processes on Unix (on Windows - ?)
+                printer::print_warning("Timeout. Killing remaining processes.");
+                self.signal(Signal::SIGKILL);
                 break;
             }
             time::sleep(Duration::from_millis(500)).await;
         }
+    }
 
-        Ok(())
+    // Windows processes can't be interrupted from the outside, so whole trees are killed right away
+    #[cfg(windows)]
+    pub async fn stop(&self) {
+        for pid in &self.pids {
+            let res = Command::new("taskkill")
+                .args(&["/T", "/F", "/PID", &pid.to_string()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await;
+            if let Err(error) = res {
+                printer::print_warning(&format!("Failed to kill process tree {}: {}", pid, error));
+            }
+        }
+        let expire = Instant::now() + Process::timeout();
+        while self.exited_processes.load(Ordering::Relaxed) < self.size && Instant::now() < expire {
+            time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: Signal) {
+        for pid in &self.pids {
+            match nix::sys::signal::killpg(Pid::from_raw(*pid as i32), signal) {
+                Ok(()) | Err(NixError::Sys(Errno::ESRCH)) => (),
+                Err(error) => printer::print_warning(&format!(
+                    "Failed to send {:?} to process group {}: {}",
+                    signal, pid, error
+                )),
+            }
+        }
     }
 }
 
@@ -0,0 +1,482 @@
+// End-to-end suite. Runs an isolated stack, i.e. the API with the test env, the Webpack server
+// and a fresh database, on random ports, then runs HTTP/GraphQL scenarios from a YAML file
+// against it. The stack is torn down no matter how the suite ends, incl. Ctrl-C.
+//
+// Scenario file:
+//   scenarios:
+//     - name: Creates post
+//       steps:
+//         - graphql:
+//             query: "mutation { createPost(input: { title: \"Title\", content: \"Content\" }) { id } }"
+//           save:
+//             id: /data/createPost/id        # JSON pointer into the response body
+//         - http:
+//             target: web                    # api (default) or web
+//             method: GET                    # GET (default), POST, etc.
+//             path: /
+//           expect:
+//             status: 200
+//             body_contains: "<html"
+//
+// Saved values are available to the next steps as `${name}` in paths, bodies, queries and variables.
+// GraphQL steps expect no errors unless `expect.errors` lists the reasons, e.g. `[POST_NOT_FOUND]`,
+// and `expect.data` is matched as a subset of the response data.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use hyper::{Body, Client, Request};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{signal, time};
+
+use crate::{
+    cmd::{ProcessLog, RunningPool},
+    error, net, printer,
+    services::{api, client, docker, postgres},
+    Env, EnvData, Error, Exec, HttpAddr, PoolOutput, Result, CFG,
+};
+
+// Includes compilation of the API server, so it's generous
+const READINESS_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Deserialize)]
+struct Suite {
+    scenarios: Vec<Scenario>,
+}
+
+#[derive(Deserialize)]
+struct Scenario {
+    name: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+struct Step {
+    http: Option<Http>,
+    graphql: Option<Graphql>,
+    #[serde(default)]
+    expect: Expect,
+    #[serde(default)]
+    save: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Http {
+    #[serde(default)]
+    target: Target,
+    #[serde(default = "Http::default_method")]
+    method: String,
+    path: String,
+    body: Option<Value>,
+}
+
+impl Http {
+    fn default_method() -> String {
+        "GET".to_string()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Target {
+    Api,
+    Web,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::Api
+    }
+}
+
+#[derive(Deserialize)]
+struct Graphql {
+    query: String,
+    #[serde(default)]
+    variables: Value,
+}
+
+#[derive(Deserialize, Default)]
+struct Expect {
+    status: Option<u16>,
+    body_contains: Option<String>,
+    data: Option<Value>,
+    errors: Option<Vec<String>>,
+}
+
+struct Outcome {
+    name: String,
+    duration: Duration,
+    error: Option<String>,
+}
+
+struct Stack {
+    db: String,
+    env: EnvData,
+    api: HttpAddr,
+    web: HttpAddr,
+}
+
+impl Stack {
+    fn new() -> io::Result<Self> {
+        let host = "127.0.0.1";
+        let api_port = net::free_port()?.to_string();
+        let web_port = net::free_port()?.to_string();
+        let db = format!(
+            "{}_e2e_{:08x}",
+            CFG.pg_database(&Env::test()),
+            rand::random::<u32>()
+        );
+        let env = CFG
+            .env(&Env::test())
+            .add("API_HOST", host)
+            .add("API_PORT", &api_port)
+            .add("WEB_HOST", host)
+            .add("WEB_PORT", &web_port)
+            .add("PG_DATABASE", &db);
+
+        Ok(Self {
+            db,
+            api: HttpAddr {
+                host: host.to_string(),
+                port: api_port,
+                path: String::new(),
+            },
+            web: HttpAddr {
+                host: host.to_string(),
+                port: web_port,
+                path: "/".to_string(),
+            },
+            env,
+        })
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("http://{}:{}{}", self.api.host, self.api.port, path)
+    }
+
+    fn web_url(&self, path: &str) -> String {
+        format!("http://{}:{}{}", self.web.host, self.web.port, path)
+    }
+
+    async fn wait(&self, pool: &RunningPool) -> io::Result<()> {
+        let health = HttpAddr {
+            host: self.api.host.to_owned(),
+            port: self.api.port.to_owned(),
+            path: CFG.api_health_path(&Env::test()),
+        };
+        let expire = Instant::now() + READINESS_TIMEOUT;
+        loop {
+            if pool.has_exited_process() {
+                return Err(error::other(
+                    "Stack process exited before the stack got ready",
+                ));
+            }
+            if health.ping().await.is_ok() && self.web.ping().await.is_ok() {
+                return Ok(());
+            }
+            if Instant::now() > expire {
+                return Err(error::other("Stack didn't get ready in time"));
+            }
+            time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    async fn test(&self, pool: &RunningPool, suite: &Suite) -> io::Result<Vec<Outcome>> {
+        self.wait(pool).await?;
+        printer::print_info("Stack is ready. Running scenarios.");
+
+        let mut outcomes = vec![];
+        for scenario in &suite.scenarios {
+            let start = Instant::now();
+            let error = self.scenario(scenario).await.err();
+            outcomes.push(Outcome {
+                name: scenario.name.to_owned(),
+                duration: start.elapsed(),
+                error,
+            });
+        }
+        Ok(outcomes)
+    }
+
+    async fn scenario(&self, scenario: &Scenario) -> std::result::Result<(), String> {
+        let mut vars = HashMap::new();
+        for (idx, step) in scenario.steps.iter().enumerate() {
+            self.step(step, &mut vars)
+                .await
+                .map_err(|error| format!("Step {}: {}", idx + 1, error))?;
+        }
+        Ok(())
+    }
+
+    async fn step(
+        &self,
+        step: &Step,
+        vars: &mut HashMap<String, Value>,
+    ) -> std::result::Result<(), String> {
+        let (status, body) = match (&step.http, &step.graphql) {
+            (Some(http), None) => {
+                let path = interpolate(&http.path, vars);
+                let url = match http.target {
+                    Target::Api => self.api_url(&path),
+                    Target::Web => self.web_url(&path),
+                };
+                let body = http
+                    .body
+                    .as_ref()
+                    .map(|body| interpolate_value(body, vars).to_string());
+                send(&http.method, &url, body).await?
+            }
+            (None, Some(graphql)) => {
+                let body = serde_json::json!({
+                    "query": interpolate(&graphql.query, vars),
+                    "variables": interpolate_value(&graphql.variables, vars),
+                });
+                let url = self.api_url(&CFG.api_graphql_path(&Env::test()));
+                send("POST", &url, Some(body.to_string())).await?
+            }
+            (Some(_), Some(_)) | (None, None) => {
+                return Err("Step must have either `http` or `graphql`".to_string())
+            }
+        };
+
+        if let Some(expected) = step.expect.status {
+            if status != expected {
+                return Err(format!("Expected status {}, got {}", expected, status));
+            }
+        }
+        if let Some(expected) = &step.expect.body_contains {
+            if !body.contains(expected.as_str()) {
+                return Err(format!("Expected body to contain {:?}", expected));
+            }
+        }
+
+        let json: Option<Value> = serde_json::from_str(&body).ok();
+
+        if step.graphql.is_some() {
+            let json = json
+                .as_ref()
+                .ok_or_else(|| format!("Response is not JSON: {}", body))?;
+            let reasons = reasons(json);
+            let expected = step.expect.errors.to_owned().unwrap_or_default();
+            if reasons != expected {
+                return Err(format!("Expected errors {:?}, got {:?}", expected, reasons));
+            }
+            if let Some(expected) = &step.expect.data {
+                let expected = interpolate_value(expected, vars);
+                if !contains(&json["data"], &expected) {
+                    return Err(format!(
+                        "Expected data to contain {}, got {}",
+                        expected, json["data"]
+                    ));
+                }
+            }
+        }
+
+        for (name, pointer) in &step.save {
+            let value = json
+                .as_ref()
+                .and_then(|json| json.pointer(pointer))
+                .ok_or_else(|| format!("Nothing to save as {} at {}", name, pointer))?;
+            vars.insert(name.to_owned(), value.to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let badge = match self.error {
+            None => console::style("pass").green().bold(),
+            Some(_) => console::style("fail").red().bold(),
+        };
+        write!(
+            f,
+            "{}  {} {}",
+            badge,
+            self.name,
+            console::style(format!("({} ms)", self.duration.as_millis())).dim()
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, "\n      {}", console::style(error).red())?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(scenarios: &Path) -> Result {
+    let suite: Suite = serde_yaml::from_str(&fs::read_to_string(scenarios)?).map_err(|error| {
+        error::invalid_input(format!(
+            "Failed to parse {}: {}",
+            scenarios.display(),
+            error
+        ))
+    })?;
+    let stack = Stack::new()?;
+
+    Exec::cmd(client::rescript::make_world(None, false)).await?;
+
+    let pg_started = postgres::ensure_pg(&[Env::test()]).await?;
+    let res = match Exec::cmd(postgres::create_isolated_database(&stack.db)).await {
+        Ok(()) => {
+            let pool = Exec::spawn_pool(
+                vec![
+                    api::run_isolated(stack.env.to_owned()),
+                    client::webpack::serve_isolated(stack.env.to_owned()),
+                ],
+                PoolOutput::Captured,
+            );
+
+            let res = tokio::select! {
+                res = stack.test(&pool, &suite) => res,
+                _ = signal::ctrl_c() => Err(error::other("Interrupted")),
+            };
+
+            pool.stop().await;
+            report(pool.logs().await);
+
+            if let Err(error) = Exec::cmd(postgres::drop_isolated_database(&stack.db)).await {
+                printer::print_warning(&format!(
+                    "Failed to drop database {}: {}",
+                    stack.db,
+                    match error {
+                        Error::Io(error) => error.to_string(),
+                        Error::Piped(status) => status.to_string(),
+                        Error::NothingToExecute => "nothing to execute".to_string(),
+                    }
+                ));
+            }
+            res.map_err(Error::from)
+        }
+        Err(error) => Err(error),
+    };
+
+    if pg_started {
+        Exec::cmd(docker::compose::stop_pg()).await?;
+    }
+
+    // Outcomes are printed after process logs, so they don't get lost above them
+    let outcomes = res?;
+    for outcome in &outcomes {
+        println!("{}", outcome);
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+    if failed == 0 {
+        printer::print_info(&format!("All {} scenarios passed", outcomes.len()));
+        Ok(())
+    } else {
+        Err(error::other(format!("{} of {} scenarios failed", failed, outcomes.len())).into())
+    }
+}
+
+fn report(logs: Vec<ProcessLog>) {
+    for log in logs {
+        println!(
+            "\n{}",
+            console::style(format!("── {} logs ──", log.tag)).bold()
+        );
+        for line in log.lines {
+            println!("{}", line);
+        }
+    }
+}
+
+async fn send(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+) -> std::result::Result<(u16, String), String> {
+    let req = Request::builder()
+        .method(method)
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(body.map(Body::from).unwrap_or_else(Body::empty))
+        .map_err(|error| format!("Invalid request to {}: {}", url, error))?;
+    let res = Client::new()
+        .request(req)
+        .await
+        .map_err(|error| format!("Request to {} failed: {}", url, error))?;
+    let status = res.status().as_u16();
+    let body = hyper::body::to_bytes(res.into_body())
+        .await
+        .map_err(|error| format!("Failed to read response from {}: {}", url, error))?;
+    Ok((status, String::from_utf8_lossy(&body).to_string()))
+}
+
+// Reasons as they're reported to the client, e.g. `POST_NOT_FOUND`
+fn reasons(res: &Value) -> Vec<String> {
+    res["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|error| {
+                    error
+                        .pointer("/extensions/details/reason")
+                        .or_else(|| error.get("message"))
+                        .and_then(Value::as_str)
+                        .unwrap_or("UNKNOWN")
+                        .to_string()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Objects match when every expected key matches, arrays and scalars must be equal
+fn contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .map(|actual| contains(actual, value))
+                .unwrap_or(false)
+        }),
+        (Value::Array(actual), Value::Array(expected)) => {
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(actual, expected)| contains(actual, expected))
+        }
+        (actual, expected) => actual == expected,
+    }
+}
+
+fn interpolate(input: &str, vars: &HashMap<String, Value>) -> String {
+    vars.iter().fold(input.to_string(), |acc, (name, value)| {
+        let value = match value {
+            Value::String(value) => value.to_owned(),
+            value => value.to_string(),
+        };
+        acc.replace(&format!("${{{}}}", name), &value)
+    })
+}
+
+fn interpolate_value(input: &Value, vars: &HashMap<String, Value>) -> Value {
+    match input {
+        Value::String(value) => Value::String(interpolate(value, vars)),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| interpolate_value(value, vars))
+                .collect(),
+        ),
+        Value::Object(values) => Value::Object(
+            values
+                .iter()
+                .map(|(key, value)| (key.to_owned(), interpolate_value(value, vars)))
+                .collect(),
+        ),
+        value => value.to_owned(),
+    }
+}
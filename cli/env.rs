@@ -1,20 +1,78 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, fs};
 
-#[derive(Copy, Clone)]
-pub enum Env {
-    Dev,
-    Prod,
-    Test,
-}
+use crate::Dir;
+
+// Environment is identified by its name and configured by `env/.env.<name>` on top of `env/.env`.
+// Besides the built-in ones, any env with a file in `env/` is available, e.g. `staging` or `perf`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Env(String);
 
 impl Env {
-    pub fn to_str(&self) -> &'static str {
-        match self {
-            Env::Dev => "development",
-            Env::Prod => "production",
-            Env::Test => "test",
+    const DEV: &'static str = "development";
+    const PROD: &'static str = "production";
+    const TEST: &'static str = "test";
+
+    pub fn dev() -> Self {
+        Self(Self::DEV.to_string())
+    }
+
+    pub fn prod() -> Self {
+        Self(Self::PROD.to_string())
+    }
+
+    pub fn test() -> Self {
+        Self(Self::TEST.to_string())
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::dev(), Self::prod(), Self::test()]
+    }
+
+    // Accepts short names of the built-in envs. Whether the env exists is up to `Cfg`.
+    pub fn new(name: &str) -> Self {
+        match name {
+            "dev" => Self::dev(),
+            "prod" => Self::prod(),
+            name => Self(name.to_string()),
         }
     }
+
+    pub fn to_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_prod(&self) -> bool {
+        self.0 == Self::PROD
+    }
+
+    // Names of envs that have a file in `env/` named `<prefix><name><suffix>`
+    pub fn discover(prefix: &str, suffix: &str) -> Vec<Self> {
+        let mut envs = vec![];
+        if let Ok(entries) = fs::read_dir(Dir::Env.loc()) {
+            for entry in entries.filter_map(Result::ok) {
+                let file_name = entry.file_name();
+                let name = file_name
+                    .to_str()
+                    .and_then(|name| name.strip_prefix(prefix))
+                    .and_then(|name| name.strip_suffix(suffix));
+                // `.env.local` is a personal layer on top of `.env`, not an env
+                match name {
+                    Some(name) if !name.is_empty() && !name.contains('.') && name != "local" => {
+                        envs.push(Self(name.to_string()))
+                    }
+                    Some(_) | None => continue,
+                }
+            }
+        }
+        envs.sort();
+        envs
+    }
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 #[derive(Clone)]
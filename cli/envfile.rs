@@ -0,0 +1,407 @@
+// Env files are parsed here rather than by `dotenv`, which expands `$VAR` while parsing
+// a single file. Values are kept as templates instead and expanded once every layer is merged,
+// so `.env.test` can say `PG_DATABASE=${PG_DATABASE}_test`.
+//
+// Supported syntax:
+//   KEY=value              unquoted, `# comment` after whitespace is stripped
+//   KEY="value\n"          double quoted, with `\n`, `\t`, `\"`, `\\` and `\$` escapes
+//   KEY='value'            single quoted, taken literally
+//   KEY=${OTHER}           value of another key
+//   KEY=${OTHER:-default}  default is used when OTHER is unset or empty
+//   KEY=${KEY}_suffix      refers to the value of KEY from the layers below
+
+use std::collections::{BTreeSet, HashMap};
+
+// Templates of every key, from the lowest layer to the highest one
+pub struct Layers {
+    defs: HashMap<String, Vec<String>>,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        Self {
+            defs: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, entries: &[(String, String)]) {
+        for (key, template) in entries {
+            self.defs
+                .entry(key.to_owned())
+                .or_insert_with(Vec::new)
+                .push(template.to_owned());
+        }
+    }
+
+    // Values that must not be expanded, e.g. decrypted secrets
+    pub fn push_literal(&mut self, entries: impl IntoIterator<Item = (String, String)>) {
+        let entries: Vec<(String, String)> = entries
+            .into_iter()
+            .map(|(key, value)| (key, value.replace('$', "$$")))
+            .collect();
+        self.push(&entries);
+    }
+
+    // Keys that fail to expand are left out and reported
+    pub fn resolve(&self) -> (HashMap<String, String>, Vec<String>) {
+        let mut resolver = Resolver {
+            defs: &self.defs,
+            stack: vec![],
+            cache: HashMap::new(),
+        };
+        let mut data = HashMap::new();
+        let mut errors = BTreeSet::new();
+        for (key, templates) in &self.defs {
+            match resolver.value(key, templates.len() - 1) {
+                Ok(value) => {
+                    data.insert(key.to_owned(), value);
+                }
+                Err(error) => {
+                    errors.insert(error);
+                }
+            }
+        }
+        (data, errors.into_iter().collect())
+    }
+}
+
+struct Resolver<'a> {
+    defs: &'a HashMap<String, Vec<String>>,
+    // Keys being expanded, to report cycles instead of overflowing the stack
+    stack: Vec<(String, usize)>,
+    cache: HashMap<(String, usize), String>,
+}
+
+impl<'a> Resolver<'a> {
+    fn value(&mut self, key: &str, level: usize) -> Result<String, String> {
+        let id = (key.to_owned(), level);
+        if let Some(value) = self.cache.get(&id) {
+            return Ok(value.to_owned());
+        }
+        if let Some(idx) = self.stack.iter().position(|entry| *entry == id) {
+            let mut cycle: Vec<&str> = self.stack[idx..]
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect();
+            cycle.push(key);
+            return Err(format!("Cycle in env variables: {}", cycle.join(" -> ")));
+        }
+
+        let defs = self.defs;
+        self.stack.push(id.clone());
+        let template = &defs[key][level];
+        let res = self.expand(template, key, level);
+        self.stack.pop();
+
+        let value = res?;
+        self.cache.insert(id, value.clone());
+        Ok(value)
+    }
+
+    // Key referencing itself gets the value from the layers below, then from the process env
+    fn lookup(&mut self, name: &str, key: &str, level: usize) -> Result<Option<String>, String> {
+        let defs = self.defs;
+        match defs.get(name) {
+            Some(_) if name == key && level > 0 => self.value(name, level - 1).map(Some),
+            Some(_) if name == key => Ok(std::env::var(name).ok()),
+            Some(templates) => self.value(name, templates.len() - 1).map(Some),
+            None => Ok(std::env::var(name).ok()),
+        }
+    }
+
+    fn expand(&mut self, template: &str, key: &str, level: usize) -> Result<String, String> {
+        let mut res = String::new();
+        let mut rest = template;
+        while let Some(idx) = rest.find('$') {
+            res.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            if rest.starts_with("$$") {
+                res.push('$');
+                rest = &rest[2..];
+            } else if rest.starts_with("${") {
+                let end = closing_brace(rest).ok_or_else(|| {
+                    format!("{} has an unterminated reference: {}", key, template)
+                })?;
+                let inner = &rest[2..end];
+                let (name, default) = match inner.find(":-") {
+                    Some(idx) => (&inner[..idx], Some(&inner[idx + 2..])),
+                    None => (inner, None),
+                };
+                if !is_key(name) {
+                    return Err(format!("{} has an invalid reference: ${{{}}}", key, inner));
+                }
+                let value = match (self.lookup(name, key, level)?, default) {
+                    (Some(value), Some(_)) if !value.is_empty() => value,
+                    (Some(value), None) => value,
+                    (_, Some(default)) => self.expand(default, key, level)?,
+                    (None, None) => {
+                        return Err(format!(
+                            "{} references {}, which is not set. Use ${{{}:-default}} if it's optional.",
+                            key, name, name
+                        ))
+                    }
+                };
+                res.push_str(&value);
+                rest = &rest[end + 1..];
+            } else {
+                // Only `${VAR}` is expanded, a lone `$` is kept as is
+                res.push('$');
+                rest = &rest[1..];
+            }
+        }
+        res.push_str(rest);
+        Ok(res)
+    }
+}
+
+// Index of `}` closing `${` at the start of input, taking nested references in defaults into account
+fn closing_brace(input: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'$' if bytes.get(idx + 1) == Some(&b'{') => {
+                depth += 1;
+                idx += 1;
+            }
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    None
+}
+
+fn is_key(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        Some(_) | None => false,
+    }
+}
+
+// Value of a template that is taken as is, without expansion
+pub fn literal(template: &str) -> String {
+    template.replace("$$", "$")
+}
+
+// Returns templates, where literal `$` is escaped as `$$`, and errors of lines that failed to parse
+pub fn parse(text: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let mut entries = vec![];
+    let mut errors = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        match parse_line(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => (),
+            Err(error) => errors.push(format!("line {}: {}", idx + 1, error)),
+        }
+    }
+    (entries, errors)
+}
+
+fn parse_line(line: &str) -> Result<Option<(String, String)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = match line.find('=') {
+        Some(idx) => (line[..idx].trim(), line[idx + 1..].trim_start()),
+        None => return Err("expected KEY=VALUE".to_string()),
+    };
+    if !is_key(key) {
+        return Err(format!("invalid key {}", key));
+    }
+    Ok(Some((key.to_owned(), parse_value(value)?)))
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+    let (template, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted
+            .find('\'')
+            .ok_or_else(|| "unterminated single quote".to_string())?;
+        (quoted[..end].replace('$', "$$"), &quoted[end + 1..])
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        let mut template = String::new();
+        let mut chars = quoted.char_indices();
+        let mut end = None;
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    end = Some(idx);
+                    break;
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => template.push('\n'),
+                    Some((_, 't')) => template.push('\t'),
+                    Some((_, '$')) => template.push_str("$$"),
+                    Some((_, c @ '"')) | Some((_, c @ '\\')) => template.push(c),
+                    Some((_, c)) => {
+                        template.push('\\');
+                        template.push(c);
+                    }
+                    None => template.push('\\'),
+                },
+                c => template.push(c),
+            }
+        }
+        let end = end.ok_or_else(|| "unterminated double quote".to_string())?;
+        (template, &quoted[end + 1..])
+    } else {
+        let end = value
+            .char_indices()
+            .find(|(idx, c)| *c == '#' && value[..*idx].ends_with(char::is_whitespace))
+            .map(|(idx, _)| idx)
+            .unwrap_or_else(|| value.len());
+        (value[..end].trim_end().to_owned(), "")
+    };
+
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(template)
+    } else {
+        Err(format!("unexpected {} after closing quote", rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(files: &[&str]) -> (HashMap<String, String>, Vec<String>) {
+        let mut layers = Layers::new();
+        for text in files {
+            let (entries, errors) = parse(text);
+            assert_eq!(errors, Vec::<String>::new());
+            layers.push(&entries);
+        }
+        layers.resolve()
+    }
+
+    fn value(files: &[&str], key: &str) -> String {
+        let (data, errors) = resolve(files);
+        assert_eq!(errors, Vec::<String>::new());
+        data[key].clone()
+    }
+
+    #[test]
+    fn unquoted_values() {
+        let text = "# comment\n\nA=foo bar # comment\nB=a#b\nexport C = c\nD=";
+        assert_eq!(value(&[text], "A"), "foo bar");
+        assert_eq!(value(&[text], "B"), "a#b");
+        assert_eq!(value(&[text], "C"), "c");
+        assert_eq!(value(&[text], "D"), "");
+    }
+
+    #[test]
+    fn single_quoted_values_are_literal() {
+        let text = r#"A='${RRD_ENVFILE_UNSET} $x \n' # comment"#;
+        assert_eq!(value(&[text], "A"), r#"${RRD_ENVFILE_UNSET} $x \n"#);
+    }
+
+    #[test]
+    fn double_quoted_escapes() {
+        let text = r#"A="a\nb\t\"c\" \\ \${RRD_ENVFILE_UNSET} \x""#;
+        assert_eq!(
+            value(&[text], "A"),
+            "a\nb\t\"c\" \\ ${RRD_ENVFILE_UNSET} \\x"
+        );
+    }
+
+    #[test]
+    fn references() {
+        let text = "HOST=localhost\nPORT=5432\nADDR=${HOST}:${PORT}\nPRICE=$5";
+        assert_eq!(value(&[text], "ADDR"), "localhost:5432");
+        assert_eq!(value(&[text], "PRICE"), "$5");
+    }
+
+    #[test]
+    fn defaults() {
+        let text = "EMPTY=\nA=${RRD_ENVFILE_UNSET:-fallback}\nB=${EMPTY:-empty}\nC=${RRD_ENVFILE_UNSET:-${EMPTY:-nested}}";
+        assert_eq!(value(&[text], "A"), "fallback");
+        assert_eq!(value(&[text], "B"), "empty");
+        assert_eq!(value(&[text], "C"), "nested");
+    }
+
+    #[test]
+    fn self_reference_takes_value_from_lower_layer() {
+        let base = "PG_DATABASE=app\nNAME=${PG_DATABASE}";
+        let overrides = "PG_DATABASE=${PG_DATABASE}_test";
+        assert_eq!(value(&[base, overrides], "PG_DATABASE"), "app_test");
+        // References from other keys see the highest layer
+        assert_eq!(value(&[base, overrides], "NAME"), "app_test");
+    }
+
+    #[test]
+    fn higher_layers_win() {
+        assert_eq!(value(&["A=base", "A=local"], "A"), "local");
+    }
+
+    #[test]
+    fn literal_layers_are_not_expanded() {
+        let (entries, _) = parse("PG_PASSWORD=plain\nA=${PG_PASSWORD}");
+        let mut layers = Layers::new();
+        layers.push(&entries);
+        layers.push_literal(vec![("PG_PASSWORD".to_string(), "p$$w${A}".to_string())]);
+        let (data, errors) = layers.resolve();
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(data["PG_PASSWORD"], "p$$w${A}");
+        assert_eq!(data["A"], "p$$w${A}");
+    }
+
+    #[test]
+    fn cycles() {
+        let (data, errors) = resolve(&["A=${B}\nB=${A}\nC=ok"]);
+        assert_eq!(data.keys().collect::<Vec<_>>(), vec!["C"]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|error| error.starts_with("Cycle in env variables")));
+    }
+
+    #[test]
+    fn unset_references() {
+        let (data, errors) = resolve(&["A=${RRD_ENVFILE_UNSET}"]);
+        assert!(data.is_empty());
+        assert_eq!(
+            errors,
+            vec!["A references RRD_ENVFILE_UNSET, which is not set. Use ${RRD_ENVFILE_UNSET:-default} if it's optional."]
+        );
+    }
+
+    #[test]
+    fn invalid_references() {
+        let (_, errors) = resolve(&["A=${1B}\nB=${C"]);
+        assert_eq!(
+            errors,
+            vec![
+                "A has an invalid reference: ${1B}",
+                "B has an unterminated reference: ${C",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let (entries, errors) = parse("A=\"abc\nB='abc\n1C=abc\nD\nE=\"a\" b\nF=ok");
+        assert_eq!(entries, vec![("F".to_string(), "ok".to_string())]);
+        assert_eq!(
+            errors,
+            vec![
+                "line 1: unterminated double quote",
+                "line 2: unterminated single quote",
+                "line 3: invalid key 1C",
+                "line 4: expected KEY=VALUE",
+                "line 5: unexpected b after closing quote",
+            ]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// Destructive commands against a protected database (`PG_PROTECTED=true`, e.g. production)
+// must be confirmed by typing the database name (or `--yes-i-mean-it` when nobody is
+// at the terminal) and are preceded by a dump (when the database exists).
+// Databases on remote hosts are never touched, whatever env they belong to.
+
+use std::io;
+
+use crate::{error, printer, services::postgres, Cmd, Env, CFG};
+
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "::1"];
+
+// Returns commands that must run before the destructive ones
+pub fn destructive(env: &Env, action: &str, confirmed: bool) -> io::Result<Vec<Cmd>> {
+    let host = CFG.pg_host(env);
+    if !LOCAL_HOSTS.contains(&host.as_str()) {
+        return Err(error::other(format!(
+            "Refusing to {} {} database: {} is not a local host",
+            action, env, host
+        )));
+    }
+    if !CFG.pg_protected(env) {
+        return Ok(vec![]);
+    }
+
+    let db = CFG.pg_database(env);
+    if !confirmed {
+        if !console::user_attended() {
+            return Err(error::other(format!(
+                "Refusing to {} {} database without confirmation. Pass --yes-i-mean-it to proceed.",
+                action, env
+            )));
+        }
+        printer::print_warning(&format!(
+            "You are about to {} {} database. Type its name ({}) to continue:",
+            action, env, db
+        ));
+        let input = console::Term::stderr().read_line()?;
+        if input.trim() != db {
+            return Err(error::other("Database name doesn't match. Aborting."));
+        }
+    }
+
+    let file = postgres::dump_file(env)?;
+    printer::print_info(&format!(
+        "Database will be dumped to {} first, unless it doesn't exist yet",
+        file
+    ));
+    Ok(vec![postgres::dump_existing_database(env, &file)])
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::Env;
+
 lazy_static! {
     static ref ROOT: Root = Root::new();
 }
@@ -34,8 +36,12 @@ pub enum Dir {
     Root,
     Env,
     Api,
+    Migrations,
     Client,
+    E2e,
     ClientCfg,
+    Certs,
+    Backups,
 }
 
 impl Dir {
@@ -44,8 +50,12 @@ impl Dir {
             Dir::Root => ROOT.path(),
             Dir::Env => Dir::Root.loc().join("env"),
             Dir::Api => Dir::Root.loc().join("api"),
+            Dir::Migrations => Dir::Api.loc().join("migrations"),
             Dir::Client => Dir::Root.loc().join("client"),
+            Dir::E2e => Dir::Root.loc().join("e2e"),
             Dir::ClientCfg => Dir::Client.loc().join("cfg"),
+            Dir::Certs => Dir::Root.loc().join("certs"),
+            Dir::Backups => Dir::Root.loc().join("backups"),
         }
     }
 
@@ -68,14 +78,17 @@ impl Dir {
 pub enum File {
     Env,
     EnvExample,
-    DevEnv,
-    DevEnvExample,
-    ProdEnv,
-    ProdEnvExample,
-    TestEnv,
-    TestEnvExample,
-    WebpackDevConfig,
-    WebpackProdConfig,
+    EnvLocal,
+    EnvOverrides(Env),
+    EnvOverridesLocal(Env),
+    EnvOverridesExample(Env),
+    WebpackConfig(Env),
+    Secrets(Env),
+    SecretsKey(Env),
+    DevTlsCert,
+    DevTlsKey,
+    GraphqlSchema,
+    E2eScenarios,
 }
 
 impl File {
@@ -83,14 +96,19 @@ impl File {
         match self {
             File::Env => Dir::Env.loc().join(".env"),
             File::EnvExample => Dir::Env.loc().join("env.example"),
-            File::DevEnv => Dir::Env.loc().join(".env.development"),
-            File::DevEnvExample => Dir::Env.loc().join("env.development.example"),
-            File::ProdEnv => Dir::Env.loc().join(".env.production"),
-            File::ProdEnvExample => Dir::Env.loc().join("env.production.example"),
-            File::TestEnv => Dir::Env.loc().join(".env.test"),
-            File::TestEnvExample => Dir::Env.loc().join("env.test.example"),
-            File::WebpackDevConfig => Dir::ClientCfg.loc().join("webpack.development.config.js"),
-            File::WebpackProdConfig => Dir::ClientCfg.loc().join("webpack.production.config.js"),
+            File::EnvLocal => Dir::Env.loc().join(".env.local"),
+            File::EnvOverrides(env) => Dir::Env.loc().join(format!(".env.{}", env)),
+            File::EnvOverridesLocal(env) => Dir::Env.loc().join(format!(".env.{}.local", env)),
+            File::EnvOverridesExample(env) => Dir::Env.loc().join(format!("env.{}.example", env)),
+            File::WebpackConfig(env) => Dir::ClientCfg
+                .loc()
+                .join(format!("webpack.{}.config.js", env)),
+            File::Secrets(env) => Dir::Env.loc().join(format!("secrets.{}.enc", env)),
+            File::SecretsKey(env) => Dir::Env.loc().join(format!(".secrets.{}.key", env)),
+            File::DevTlsCert => Dir::Certs.loc().join("localhost.crt"),
+            File::DevTlsKey => Dir::Certs.loc().join("localhost.key"),
+            File::GraphqlSchema => Dir::Client.loc().join("graphql_schema.json"),
+            File::E2eScenarios => Dir::E2e.loc().join("scenarios.yml"),
         }
     }
 
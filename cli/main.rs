@@ -4,18 +4,24 @@ extern crate lazy_static;
 mod app;
 mod cfg;
 mod cmd;
+mod e2e;
 mod env;
+mod envfile;
+mod guard;
 mod loc;
 mod net;
 mod printer;
 mod result;
+mod schema;
+mod secrets;
 mod services;
+mod spec;
 
 use std::process;
 
 use app::App;
 use cfg::CFG;
-use cmd::{Cmd, Exec, Process};
+use cmd::{Cmd, Exec, PoolOutput, Process};
 use env::{Env, EnvData};
 use loc::{Dir, File};
 use net::{HttpAddr, TcpAddr};
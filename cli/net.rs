@@ -1,6 +1,6 @@
 use std::{
     io,
-    net::{Shutdown, SocketAddr},
+    net::{Shutdown, SocketAddr, TcpListener},
     time::Duration,
 };
 
@@ -71,4 +71,36 @@ impl TcpAddr {
             ))),
         }
     }
+
+    // Unlike `wait`, resolves host names and gives up after the timeout,
+    // since it's used for servers that aren't started by this CLI
+    pub async fn wait_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let addr = self.format();
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            match TcpStream::connect(addr.as_str()).await {
+                Ok(stream) => {
+                    if let Err(error) = stream.shutdown(Shutdown::Both) {
+                        eprintln!("Failed to close socket: {}", error);
+                    };
+                    return Ok(());
+                }
+                Err(_) if time::Instant::now() < deadline => {
+                    time::sleep(Duration::from_millis(250)).await
+                }
+                Err(error) => {
+                    return Err(error::other(format!(
+                        "{} is not reachable: {}",
+                        addr, error
+                    )))
+                }
+            }
+        }
+    }
+}
+
+// Port is released right away, so there's a tiny chance something else grabs it before it's used
+pub fn free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
 }
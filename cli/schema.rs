@@ -0,0 +1,473 @@
+// Compares two introspection results produced by `api schema json`.
+// Breaking changes are the ones that can fail a query or a mutation written against the old schema.
+
+use std::{collections::BTreeMap, fmt, io};
+
+use serde_json::Value;
+
+use crate::error;
+
+pub enum Severity {
+    Breaking,
+    Safe,
+}
+
+pub struct Change {
+    pub severity: Severity,
+    pub msg: String,
+}
+
+impl Change {
+    fn breaking(msg: String) -> Self {
+        Self {
+            severity: Severity::Breaking,
+            msg,
+        }
+    }
+
+    fn safe(msg: String) -> Self {
+        Self {
+            severity: Severity::Safe,
+            msg,
+        }
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        match self.severity {
+            Severity::Breaking => true,
+            Severity::Safe => false,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let badge = match self.severity {
+            Severity::Breaking => console::style("breaking").red().bold(),
+            Severity::Safe => console::style("safe").green().bold(),
+        };
+        write!(f, "{:>8}  {}", badge, self.msg)
+    }
+}
+
+pub fn diff(old: &str, new: &str) -> io::Result<Vec<Change>> {
+    let old = parse(old)?;
+    let new = parse(new)?;
+    let mut changes = vec![];
+
+    let old_types = named(&old["__schema"]["types"]);
+    let new_types = named(&new["__schema"]["types"]);
+
+    for (name, old_ty) in &old_types {
+        if name.starts_with("__") {
+            continue;
+        }
+        let new_ty = match new_types.get(name) {
+            Some(ty) => ty,
+            None => {
+                changes.push(Change::breaking(format!("Type {} was removed", name)));
+                continue;
+            }
+        };
+        let (old_kind, new_kind) = (kind(old_ty), kind(new_ty));
+        if old_kind != new_kind {
+            changes.push(Change::breaking(format!(
+                "Type {} changed kind from {} to {}",
+                name, old_kind, new_kind
+            )));
+            continue;
+        }
+        match old_kind {
+            "OBJECT" | "INTERFACE" => diff_fields(name, old_ty, new_ty, &mut changes),
+            "INPUT_OBJECT" => diff_input_fields(name, old_ty, new_ty, &mut changes),
+            "ENUM" => diff_members(name, "Value", "enumValues", old_ty, new_ty, &mut changes),
+            "UNION" => diff_members(
+                name,
+                "Member",
+                "possibleTypes",
+                old_ty,
+                new_ty,
+                &mut changes,
+            ),
+            _ => (),
+        }
+    }
+    for name in new_types.keys() {
+        if !name.starts_with("__") && !old_types.contains_key(name) {
+            changes.push(Change::safe(format!("Type {} was added", name)));
+        }
+    }
+
+    let old_directives = named(&old["__schema"]["directives"]);
+    let new_directives = named(&new["__schema"]["directives"]);
+    for name in old_directives.keys() {
+        if !new_directives.contains_key(name) {
+            changes.push(Change::breaking(format!("Directive @{} was removed", name)));
+        }
+    }
+
+    Ok(changes)
+}
+
+fn parse(input: &str) -> io::Result<Value> {
+    let value: Value = serde_json::from_str(input)
+        .map_err(|error| error::other(format!("Failed to parse GraphQL schema: {}", error)))?;
+    if value["__schema"].is_object() {
+        Ok(value)
+    } else {
+        Err(error::other(
+            "GraphQL schema doesn't look like an introspection result",
+        ))
+    }
+}
+
+fn diff_fields(ty: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    let old_fields = named(&old["fields"]);
+    let new_fields = named(&new["fields"]);
+
+    for (name, old_field) in &old_fields {
+        let path = format!("{}.{}", ty, name);
+        let new_field = match new_fields.get(name) {
+            Some(field) => field,
+            None => {
+                changes.push(Change::breaking(format!("Field {} was removed", path)));
+                continue;
+            }
+        };
+
+        let (old_ref, new_ref) = (&old_field["type"], &new_field["type"]);
+        if old_ref != new_ref {
+            let msg = format!(
+                "Field {} changed type from {} to {}",
+                path,
+                render(old_ref),
+                render(new_ref)
+            );
+            changes.push(if is_safe_output_change(old_ref, new_ref) {
+                Change::safe(msg)
+            } else {
+                Change::breaking(msg)
+            });
+        }
+
+        diff_input_values(
+            &format!("Argument {}", path),
+            &old_field["args"],
+            &new_field["args"],
+            changes,
+        );
+    }
+    for name in new_fields.keys() {
+        if !old_fields.contains_key(name) {
+            changes.push(Change::safe(format!("Field {}.{} was added", ty, name)));
+        }
+    }
+
+    let new_interfaces = named(&new["interfaces"]);
+    for name in named(&old["interfaces"]).keys() {
+        if !new_interfaces.contains_key(name) {
+            changes.push(Change::breaking(format!(
+                "Type {} no longer implements {}",
+                ty, name
+            )));
+        }
+    }
+}
+
+fn diff_input_fields(ty: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    diff_input_values(
+        &format!("Input field {}", ty),
+        &old["inputFields"],
+        &new["inputFields"],
+        changes,
+    );
+}
+
+// Shared by field arguments and input object fields: both are `__InputValue`
+fn diff_input_values(prefix: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    let old_values = named(old);
+    let new_values = named(new);
+
+    for (name, old_value) in &old_values {
+        let path = format!("{}.{}", prefix, name);
+        let new_value = match new_values.get(name) {
+            Some(value) => value,
+            None => {
+                changes.push(Change::breaking(format!("{} was removed", path)));
+                continue;
+            }
+        };
+        let (old_ref, new_ref) = (&old_value["type"], &new_value["type"]);
+        if old_ref != new_ref {
+            let msg = format!(
+                "{} changed type from {} to {}",
+                path,
+                render(old_ref),
+                render(new_ref)
+            );
+            changes.push(if is_safe_input_change(old_ref, new_ref) {
+                Change::safe(msg)
+            } else {
+                Change::breaking(msg)
+            });
+        }
+    }
+    for (name, new_value) in &new_values {
+        if old_values.contains_key(name) {
+            continue;
+        }
+        let path = format!("{}.{}", prefix, name);
+        if kind(&new_value["type"]) == "NON_NULL" && new_value["defaultValue"].is_null() {
+            changes.push(Change::breaking(format!("{} was added as required", path)));
+        } else {
+            changes.push(Change::safe(format!("{} was added", path)));
+        }
+    }
+}
+
+fn diff_members(
+    ty: &str,
+    label: &str,
+    key: &str,
+    old: &Value,
+    new: &Value,
+    changes: &mut Vec<Change>,
+) {
+    let old_members = named(&old[key]);
+    let new_members = named(&new[key]);
+
+    for name in old_members.keys() {
+        if !new_members.contains_key(name) {
+            changes.push(Change::breaking(format!(
+                "{} {}.{} was removed",
+                label, ty, name
+            )));
+        }
+    }
+    for name in new_members.keys() {
+        if !old_members.contains_key(name) {
+            changes.push(Change::safe(format!("{} {}.{} was added", label, ty, name)));
+        }
+    }
+}
+
+// Clients can handle an output that became stricter, but not the one that became nullable
+fn is_safe_output_change(old: &Value, new: &Value) -> bool {
+    match (kind(old), kind(new)) {
+        ("NON_NULL", "NON_NULL") | ("LIST", "LIST") => {
+            is_safe_output_change(&old["ofType"], &new["ofType"])
+        }
+        (_, "NON_NULL") => is_safe_output_change(old, &new["ofType"]),
+        ("NON_NULL", _) | ("LIST", _) | (_, "LIST") => false,
+        (_, _) => old["name"] == new["name"],
+    }
+}
+
+// Clients can keep sending a value to an input that became nullable, but not the other way around
+fn is_safe_input_change(old: &Value, new: &Value) -> bool {
+    match (kind(old), kind(new)) {
+        ("NON_NULL", "NON_NULL") | ("LIST", "LIST") => {
+            is_safe_input_change(&old["ofType"], &new["ofType"])
+        }
+        ("NON_NULL", _) => is_safe_input_change(&old["ofType"], new),
+        (_, "NON_NULL") | ("LIST", _) | (_, "LIST") => false,
+        (_, _) => old["name"] == new["name"],
+    }
+}
+
+fn render(ty: &Value) -> String {
+    match kind(ty) {
+        "NON_NULL" => format!("{}!", render(&ty["ofType"])),
+        "LIST" => format!("[{}]", render(&ty["ofType"])),
+        _ => ty["name"].as_str().unwrap_or("?").to_string(),
+    }
+}
+
+fn kind(ty: &Value) -> &str {
+    ty["kind"].as_str().unwrap_or("")
+}
+
+fn named(list: &Value) -> BTreeMap<&str, &Value> {
+    list.as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item["name"].as_str().map(|name| (name, item)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn scalar(name: &str) -> Value {
+        json!({ "kind": "SCALAR", "name": name, "ofType": null })
+    }
+
+    fn non_null(ty: Value) -> Value {
+        json!({ "kind": "NON_NULL", "name": null, "ofType": ty })
+    }
+
+    fn list(ty: Value) -> Value {
+        json!({ "kind": "LIST", "name": null, "ofType": ty })
+    }
+
+    fn schema(post_fields: Value, input_fields: Value) -> String {
+        json!({
+            "__schema": {
+                "directives": [],
+                "types": [
+                    { "kind": "OBJECT", "name": "Post", "fields": post_fields, "interfaces": [] },
+                    { "kind": "INPUT_OBJECT", "name": "PostInput", "inputFields": input_fields },
+                ],
+            }
+        })
+        .to_string()
+    }
+
+    fn field(name: &str, ty: Value) -> Value {
+        json!({ "name": name, "type": ty, "args": [] })
+    }
+
+    fn input(name: &str, ty: Value) -> Value {
+        json!({ "name": name, "type": ty, "defaultValue": null })
+    }
+
+    fn severities(old: &str, new: &str) -> Vec<(bool, String)> {
+        diff(old, new)
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.is_breaking(), change.msg))
+            .collect()
+    }
+
+    #[test]
+    fn output_that_became_non_null_is_safe() {
+        let string = scalar("String");
+        assert!(is_safe_output_change(&string, &non_null(string.clone())));
+        assert!(is_safe_output_change(
+            &list(string.clone()),
+            &list(non_null(string.clone()))
+        ));
+        assert!(is_safe_output_change(
+            &list(string.clone()),
+            &non_null(list(non_null(string)))
+        ));
+    }
+
+    #[test]
+    fn output_that_became_nullable_is_breaking() {
+        let string = scalar("String");
+        assert!(!is_safe_output_change(&non_null(string.clone()), &string));
+        assert!(!is_safe_output_change(
+            &list(non_null(string.clone())),
+            &list(string)
+        ));
+    }
+
+    #[test]
+    fn output_of_another_type_or_shape_is_breaking() {
+        let string = scalar("String");
+        assert!(!is_safe_output_change(&string, &scalar("Int")));
+        assert!(!is_safe_output_change(&string, &list(string.clone())));
+        assert!(!is_safe_output_change(&list(string.clone()), &string));
+    }
+
+    #[test]
+    fn input_that_became_nullable_is_safe() {
+        let string = scalar("String");
+        assert!(is_safe_input_change(&non_null(string.clone()), &string));
+        assert!(is_safe_input_change(
+            &list(non_null(string.clone())),
+            &list(string.clone())
+        ));
+        assert!(is_safe_input_change(
+            &non_null(list(non_null(string.clone()))),
+            &list(string)
+        ));
+    }
+
+    #[test]
+    fn input_that_became_non_null_is_breaking() {
+        let string = scalar("String");
+        assert!(!is_safe_input_change(&string, &non_null(string.clone())));
+        assert!(!is_safe_input_change(
+            &list(string.clone()),
+            &list(non_null(string))
+        ));
+    }
+
+    #[test]
+    fn input_of_another_type_or_shape_is_breaking() {
+        let string = scalar("String");
+        assert!(!is_safe_input_change(&string, &scalar("Int")));
+        assert!(!is_safe_input_change(&string, &list(string.clone())));
+        assert!(!is_safe_input_change(&list(string.clone()), &string));
+    }
+
+    #[test]
+    fn same_schema_has_no_changes() {
+        let schema = schema(
+            json!([field("title", non_null(scalar("String")))]),
+            json!([input("title", non_null(scalar("String")))]),
+        );
+        assert!(diff(&schema, &schema).unwrap().is_empty());
+    }
+
+    #[test]
+    fn removed_field_is_breaking_and_added_one_is_safe() {
+        let old = schema(json!([field("title", scalar("String"))]), json!([]));
+        let new = schema(json!([field("content", scalar("String"))]), json!([]));
+        assert_eq!(
+            severities(&old, &new),
+            vec![
+                (true, "Field Post.title was removed".to_string()),
+                (false, "Field Post.content was added".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn added_required_input_is_breaking_and_optional_one_is_safe() {
+        let old = schema(json!([]), json!([]));
+        let new = schema(
+            json!([]),
+            json!([
+                input("title", non_null(scalar("String"))),
+                input("content", scalar("String")),
+            ]),
+        );
+        assert_eq!(
+            severities(&old, &new),
+            vec![
+                (false, "Input field PostInput.content was added".to_string()),
+                (
+                    true,
+                    "Input field PostInput.title was added as required".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_type_is_breaking() {
+        let old = schema(json!([]), json!([]));
+        let new = json!({ "__schema": { "directives": [], "types": [] } }).to_string();
+        assert_eq!(
+            severities(&old, &new),
+            vec![
+                (true, "Type Post was removed".to_string()),
+                (true, "Type PostInput was removed".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_input_that_is_not_introspection_result() {
+        assert!(diff("{}", "{}").is_err());
+    }
+}
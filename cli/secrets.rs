@@ -0,0 +1,280 @@
+// Secrets of an env live in `env/secrets.<env>.enc`, which is safe to commit:
+// it's an env file encrypted with AES-256-GCM, and `Cfg` merges it over the plain env files.
+// The key is taken from `RRD_SECRETS_KEY_<ENV>` (e.g. in CI) or from the git-ignored
+// `env/.secrets.<env>.key`. Keys are shared out of band, never alongside the repo.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs, io,
+    path::Path,
+};
+
+use aes_gcm::{
+    aead::{Aead, NewAead, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+
+use crate::{envfile, error, printer, Cmd, Dir, Env, EnvData, Exec, File, Result};
+
+// Prefix of the encrypted file, so the format can change without breaking existing files
+const VERSION: &str = "rrd-secrets-v1";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+pub type Secrets = BTreeMap<String, String>;
+
+// Returns `None` when the env has no secrets file
+pub fn load(env: &Env) -> io::Result<Option<HashMap<String, String>>> {
+    if !File::Secrets(env.clone()).exists() {
+        return Ok(None);
+    }
+    let secrets = parse(&read(env)?)?;
+    Ok(Some(secrets.into_iter().collect()))
+}
+
+pub fn get(env: &Env, name: &str) -> io::Result<String> {
+    parse(&read(env)?)?
+        .remove(name)
+        .ok_or_else(|| error::invalid_input(format!("{} is not set in {} secrets", name, env)))
+}
+
+pub fn set(env: &Env, name: &str, value: &str) -> io::Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(error::invalid_input(format!(
+            "Invalid secret name {}. Use upper case letters, digits and underscores.",
+            name
+        )));
+    }
+    if value.contains('\n') {
+        return Err(error::invalid_input("Multi-line secrets aren't supported"));
+    }
+    let mut secrets = parse(&read(env)?)?;
+    secrets.insert(name.to_string(), value.to_string());
+    write(env, &serialize(&secrets))
+}
+
+// Opens decrypted secrets in `$VISUAL`/`$EDITOR` and encrypts them back on save
+pub async fn edit(env: &Env) -> Result {
+    let before = read(env)?;
+    let tmp = env::temp_dir().join(format!(
+        "rrd-secrets-{}-{:016x}.env",
+        env,
+        rand::random::<u64>()
+    ));
+    write_private(&tmp, before.as_bytes())?;
+
+    let res = edit_file(env, &tmp, &before).await;
+    fs::remove_file(&tmp).ok();
+    res
+}
+
+async fn edit_file(env: &Env, tmp: &Path, before: &str) -> Result {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    Exec::cmd(Cmd {
+        run: format!("{} '{}'", editor, tmp.display()),
+        env: EnvData::empty(),
+        dir: Dir::Root,
+        msg: format!("Editing {} secrets", env),
+    })
+    .await?;
+
+    let after = fs::read_to_string(tmp)?;
+    if after == before {
+        printer::print_info("Secrets are unchanged");
+        return Ok(());
+    }
+    parse(&after)?;
+    write(env, &after)?;
+    Ok(())
+}
+
+// Re-encrypts secrets with a new key, which is written to the key file
+pub fn rotate(env: &Env) -> io::Result<()> {
+    let text = read(env)?;
+    let key = rand::random::<[u8; KEY_LEN]>();
+
+    // New key is staged first, so a failure in between never leaves secrets unreadable
+    let key_file = File::SecretsKey(env.clone()).loc();
+    let staged = key_file.with_extension("key.new");
+    write_private(&staged, base64::encode(key).as_bytes())?;
+    fs::write(File::Secrets(env.clone()).loc(), encrypt(env, &key, &text)?)?;
+    fs::rename(&staged, &key_file)?;
+
+    if env::var(key_var(env)).is_ok() {
+        printer::print_warning(&format!(
+            "{} is set and takes precedence over the key file. Update it with the new key from {}.",
+            key_var(env),
+            key_file.display()
+        ));
+    }
+    Ok(())
+}
+
+// Plain text of secrets, empty when the env has no secrets file yet
+fn read(env: &Env) -> io::Result<String> {
+    let file = File::Secrets(env.clone());
+    if !file.exists() {
+        return Ok(String::new());
+    }
+    decrypt(env, &key(env)?, &fs::read_to_string(file.loc())?)
+}
+
+fn write(env: &Env, text: &str) -> io::Result<()> {
+    let key = match key(env) {
+        Ok(key) => key,
+        Err(_) if !File::Secrets(env.clone()).exists() => generate_key(env)?,
+        Err(error) => return Err(error),
+    };
+    fs::write(File::Secrets(env.clone()).loc(), encrypt(env, &key, text)?)
+}
+
+// Secrets are taken literally, `${VAR}` in them isn't expanded
+fn parse(text: &str) -> io::Result<Secrets> {
+    let (entries, failures) = envfile::parse(text);
+    if let Some(failure) = failures.first() {
+        return Err(error::other(format!(
+            "Failed to parse secrets at {}",
+            failure
+        )));
+    }
+    Ok(entries
+        .into_iter()
+        .map(|(name, template)| (name, envfile::literal(&template)))
+        .collect())
+}
+
+// Single quotes keep values literal, double quotes are used only for values containing them
+fn serialize(secrets: &Secrets) -> String {
+    let mut text = String::new();
+    for (name, value) in secrets {
+        if value.contains('\'') {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$");
+            text.push_str(&format!("{}=\"{}\"\n", name, escaped));
+        } else {
+            text.push_str(&format!("{}='{}'\n", name, value));
+        }
+    }
+    text
+}
+
+fn key_var(env: &Env) -> String {
+    format!(
+        "RRD_SECRETS_KEY_{}",
+        env.to_str().to_uppercase().replace('-', "_")
+    )
+}
+
+fn key(env: &Env) -> io::Result<[u8; KEY_LEN]> {
+    let file = File::SecretsKey(env.clone());
+    let encoded = match env::var(key_var(env)) {
+        Ok(key) => key,
+        Err(_) if file.exists() => fs::read_to_string(file.loc())?,
+        Err(_) => {
+            return Err(error::other(format!(
+                "No key for {} secrets. Set {} or put the key into env/{}.",
+                env,
+                key_var(env),
+                file.file_name()
+            )))
+        }
+    };
+    let bytes = base64::decode(encoded.trim())
+        .map_err(|_| error::other(format!("Key for {} secrets is not valid base64", env)))?;
+    if bytes.len() != KEY_LEN {
+        return Err(error::other(format!(
+            "Key for {} secrets must be {} bytes long",
+            env, KEY_LEN
+        )));
+    }
+    let mut key = [0; KEY_LEN];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+fn generate_key(env: &Env) -> io::Result<[u8; KEY_LEN]> {
+    let key = rand::random::<[u8; KEY_LEN]>();
+    let file = File::SecretsKey(env.clone());
+    write_private(&file.loc(), base64::encode(key).as_bytes())?;
+    printer::print_info(&format!(
+        "Generated a key for {} secrets in env/{}. Share it with the team out of band.",
+        env,
+        file.file_name()
+    ));
+    Ok(key)
+}
+
+// Env name is authenticated along with the secrets, so files can't be swapped between envs
+fn encrypt(env: &Env, key: &[u8; KEY_LEN], text: &str) -> io::Result<String> {
+    let cipher = Aes256Gcm::new(Key::from_slice(key));
+    let nonce = rand::random::<[u8; NONCE_LEN]>();
+    let payload = Payload {
+        msg: text.as_bytes(),
+        aad: env.to_str().as_bytes(),
+    };
+    let sealed = cipher
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| error::other(format!("Failed to encrypt {} secrets", env)))?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend(sealed);
+    Ok(format!("{}:{}\n", VERSION, base64::encode(bytes)))
+}
+
+fn decrypt(env: &Env, key: &[u8; KEY_LEN], content: &str) -> io::Result<String> {
+    let invalid = || error::other(format!("env/secrets.{}.enc is corrupted", env));
+    let encoded = content
+        .trim()
+        .strip_prefix(VERSION)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .ok_or_else(invalid)?;
+    let bytes = base64::decode(encoded).map_err(|_| invalid())?;
+    if bytes.len() < NONCE_LEN {
+        return Err(invalid());
+    }
+    let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(Key::from_slice(key));
+    let payload = Payload {
+        msg: sealed,
+        aad: env.to_str().as_bytes(),
+    };
+    let text = cipher
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| {
+            error::other(format!(
+                "Failed to decrypt {} secrets. The key is wrong or the file was tampered with.",
+                env
+            ))
+        })?;
+    String::from_utf8(text).map_err(|_| invalid())
+}
+
+// Key files and decrypted copies must be readable by the owner only
+fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // `mode` only applies to new files, an existing one keeps its permissions otherwise
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(bytes)
+}
@@ -1,11 +1,11 @@
-use crate::{Cmd, Dir, Env, EnvData, Process, CFG};
+use crate::{Cmd, Dir, Env, EnvData, File, Process, CFG};
 
 pub fn build_dev() -> Cmd {
     Cmd {
         run: "cargo build --package=api".to_string(),
         env: EnvData::empty(),
         dir: Dir::Root,
-        msg: "Building API server",
+        msg: "Building API server".to_string(),
     }
 }
 
@@ -14,7 +14,7 @@ pub fn build_release() -> Cmd {
         run: "cargo build --package=api --release".to_string(),
         env: EnvData::empty(),
         dir: Dir::Root,
-        msg: "Building API server release",
+        msg: "Building API server release".to_string(),
     }
 }
 
@@ -23,55 +23,158 @@ pub fn clean() -> Cmd {
         run: "cargo clean --package=api".to_string(),
         env: EnvData::empty(),
         dir: Dir::Root,
-        msg: "Cleaning API",
+        msg: "Cleaning API".to_string(),
     }
 }
 
-pub fn run_dev() -> Process {
+// Each test creates its own database, see `api/src/testing.rs`
+pub fn test() -> Cmd {
+    Cmd {
+        run: "cargo test --package=api --color=always".to_string(),
+        env: CFG.env(&Env::test()),
+        dir: Dir::Root,
+        msg: "Running API tests".to_string(),
+    }
+}
+
+// Migrations are applied on boot, since the database of an isolated stack is created empty
+pub fn run_isolated(env: EnvData) -> Process {
     Process::new(
         "api",
         Cmd {
-            run: "cargo run --package=api --color=always".to_string(),
-            env: CFG.env(&Env::Dev),
+            run: "cargo run --package=api --quiet -- --migrate".to_string(),
+            env,
             dir: Dir::Root,
-            msg: "Running development API server",
+            msg: "Running isolated API server".to_string(),
         },
     )
 }
 
-pub fn run_release() -> Process {
+pub fn run(env: &Env, release: bool) -> Process {
     Process::new(
         "api",
         Cmd {
-            run: "cargo run --package=api --release --color=always".to_string(),
-            env: CFG.env(&Env::Prod),
+            run: if release {
+                "cargo run --package=api --release --color=always".to_string()
+            } else {
+                "cargo run --package=api --color=always".to_string()
+            },
+            env: CFG.env(env),
             dir: Dir::Root,
-            msg: "Running release build of API server",
+            msg: if release {
+                format!("Running release build of {} API server", env)
+            } else {
+                format!("Running {} API server", env)
+            },
         },
     )
 }
 
-pub fn watch_dev() -> Process {
+pub fn watch(env: &Env, release: bool) -> Process {
     Process::new(
         "api",
         Cmd {
-            run: "cargo watch --watch api --exec 'run --package=api --color=always'".to_string(),
-            env: CFG.env(&Env::Dev),
+            run: if release {
+                "cargo watch --watch api --exec 'run --package=api --release --color=always'"
+                    .to_string()
+            } else {
+                "cargo watch --watch api --exec 'run --package=api --color=always'".to_string()
+            },
+            env: CFG.env(env),
             dir: Dir::Root,
-            msg: "Running reloadable development API server",
+            msg: if release {
+                format!("Running reloadable release build of {} API server", env)
+            } else {
+                format!("Running reloadable {} API server", env)
+            },
         },
     )
 }
 
-pub fn watch_release() -> Process {
-    Process::new(
-        "api",
-        Cmd {
-            run: "cargo watch --watch api --exec 'run --package=api --release --color=always'"
-                .to_string(),
-            env: CFG.env(&Env::Prod),
-            dir: Dir::Root,
-            msg: "Running reloadable release build of API server",
+pub fn print_schema_sdl() -> Cmd {
+    Cmd {
+        run: "cargo run --package=api --quiet -- schema sdl".to_string(),
+        env: EnvData::empty(),
+        dir: Dir::Root,
+        msg: "Printing GraphQL schema".to_string(),
+    }
+}
+
+pub fn print_schema_json() -> Cmd {
+    Cmd {
+        run: "cargo run --package=api --quiet -- schema json".to_string(),
+        env: EnvData::empty(),
+        dir: Dir::Root,
+        msg: "Generating GraphQL schema".to_string(),
+    }
+}
+
+pub fn export_posts(env: &Env, format: &str, out: Option<String>) -> Cmd {
+    Cmd {
+        run: match out {
+            Some(out) => format!(
+                "cargo run --package=api --quiet -- posts export --format {} --out {}",
+                Cmd::quote(format),
+                Cmd::quote(&out)
+            ),
+            None => format!(
+                "cargo run --package=api --quiet -- posts export --format {}",
+                Cmd::quote(format)
+            ),
         },
-    )
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: format!("Exporting posts from {} database", env),
+    }
+}
+
+pub fn import_posts(env: &Env, format: &str, path: String, dry_run: bool) -> Cmd {
+    Cmd {
+        run: format!(
+            "cargo run --package=api --quiet -- posts import --format {format} {dry_run}{path}",
+            format = Cmd::quote(format),
+            dry_run = if dry_run { "--dry-run " } else { "" },
+            path = Cmd::quote(&path),
+        ),
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: if dry_run {
+            format!("Checking posts import into {} database", env)
+        } else {
+            format!("Importing posts into {} database", env)
+        },
+    }
+}
+
+pub fn seed(env: &Env, count: Option<&str>, seed: Option<&str>) -> Cmd {
+    let mut run = "cargo run --package=api --quiet -- seed".to_string();
+    if let Some(count) = count {
+        run.push_str(&format!(" --count {}", Cmd::quote(count)));
+    }
+    if let Some(seed) = seed {
+        run.push_str(&format!(" --seed {}", Cmd::quote(seed)));
+    }
+    Cmd {
+        run,
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: format!("Seeding {} database", env),
+    }
+}
+
+pub fn generate_dev_cert() -> Cmd {
+    let root = Dir::Root;
+    Cmd {
+        run: format!(
+            "mkdir -p {dir} && openssl req -x509 -newkey rsa:2048 -nodes -sha256 -days 365 \
+             -subj '/CN=localhost' -addext 'subjectAltName=DNS:localhost,IP:127.0.0.1' \
+             -keyout {key} -out {cert}",
+            dir = Dir::Certs.loc().display(),
+            key = File::DevTlsKey.relative_to(&root),
+            cert = File::DevTlsCert.relative_to(&root),
+        ),
+        env: EnvData::empty(),
+        dir: root,
+        msg: "Generating self-signed TLS certificate".to_string(),
+    }
 }
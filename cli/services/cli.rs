@@ -13,6 +13,7 @@ pub fn release(ctx: ReleaseCtx) -> Cmd {
         msg: match ctx {
             ReleaseCtx::Install => "Installing CLI",
             ReleaseCtx::Update => "Updating CLI",
-        },
+        }
+        .to_string(),
     }
 }
@@ -65,7 +65,7 @@ pub mod rescript {
             },
             env: self::env(log_level),
             dir: Dir::Client,
-            msg: "Building ReScript app",
+            msg: "Building ReScript app".to_string(),
         }
     }
 
@@ -76,7 +76,7 @@ pub mod rescript {
                 run: "bsb -w".to_string(),
                 env: self::env(log_level),
                 dir: Dir::Client,
-                msg: "Watching ReScript app",
+                msg: "Watching ReScript app".to_string(),
             },
         )
     }
@@ -92,7 +92,7 @@ pub mod rescript {
                 },
                 env: self::env(log_level),
                 dir: Dir::Client,
-                msg: "Building and watching ReScript app",
+                msg: "Building and watching ReScript app".to_string(),
             },
         )
     }
@@ -102,13 +102,13 @@ pub mod rescript {
             run: "bsb -clean-world".to_string(),
             env: self::env(None),
             dir: Dir::Client,
-            msg: "Cleaning ReScript app",
+            msg: "Cleaning ReScript app".to_string(),
         }
     }
 }
 
 pub mod webpack {
-    use crate::{Cmd, Dir, Env, File, Process, CFG};
+    use crate::{Cmd, Dir, Env, EnvData, File, Process, CFG};
 
     // TODO: Extract production build into own function with own server
     pub fn serve(env: &Env) -> Process {
@@ -121,42 +121,55 @@ pub mod webpack {
                     "webpack serve --host {host} --port {port} --config {config}",
                     host = CFG.web_host(env),
                     port = CFG.web_port(env),
-                    config = match env {
-                        Env::Dev => File::WebpackDevConfig.relative_to(&client_dir),
-                        Env::Prod => File::WebpackProdConfig.relative_to(&client_dir),
-                        Env::Test => unimplemented!(),
-                    }
+                    config = config(env).relative_to(&client_dir),
                 ),
                 env: super::env()
                     .merge(CFG.env(env))
-                    .add("NODE_ENV", env.to_str()),
+                    .add("NODE_ENV", node_env(env)),
                 dir: client_dir,
-                msg: match env {
-                    Env::Dev => "Running Webpack development server",
-                    Env::Prod => "Running Webpack production server",
-                    Env::Test => "Running Webpack test server",
-                },
+                msg: format!("Running Webpack {} server", env),
             },
         )
     }
-}
 
-pub mod graphql {
-    use crate::{Cmd, Dir, Env, CFG};
+    // Test build served on ports of the isolated stack of `rrd e2e`
+    pub fn serve_isolated(env: EnvData) -> Process {
+        let client_dir = Dir::Client;
 
-    pub fn write_schema(env: &Env) -> Cmd {
-        Cmd {
-            run: format!(
-                "get-graphql-schema {} --json > graphql_schema.json",
-                CFG.api_graphql_url(env).format()
-            ),
-            env: super::env(),
-            dir: Dir::Client,
-            msg: match env {
-                Env::Dev => "Writing GraphQL schema against development server",
-                Env::Prod => "Writing GraphQL schema against production server",
-                Env::Test => "Writing GraphQL schema against test server",
+        Process::new(
+            "webpack",
+            Cmd {
+                run: format!(
+                    "webpack serve --host {host} --port {port} --config {config}",
+                    host = env.get("WEB_HOST").expect("Failed to get WEB_HOST"),
+                    port = env.get("WEB_PORT").expect("Failed to get WEB_PORT"),
+                    config = config(&Env::test()).relative_to(&client_dir),
+                ),
+                env: super::env()
+                    .merge(env)
+                    .add("NODE_ENV", node_env(&Env::test())),
+                dir: client_dir,
+                msg: "Running isolated Webpack server".to_string(),
             },
+        )
+    }
+
+    // Libraries only know production and development, so other envs, e.g. `staging`, get the latter
+    fn node_env(env: &Env) -> &'static str {
+        if env.is_prod() {
+            "production"
+        } else {
+            "development"
+        }
+    }
+
+    // Envs without own config, e.g. `staging`, are built like development
+    fn config(env: &Env) -> File {
+        let config = File::WebpackConfig(env.clone());
+        if config.exists() {
+            config
+        } else {
+            File::WebpackConfig(Env::dev())
         }
     }
 }
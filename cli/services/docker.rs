@@ -19,7 +19,7 @@ pub mod compose {
                 run: "docker-compose up".to_string(),
                 env: EnvData::empty(),
                 dir: Dir::Root,
-                msg: "Running Docker services",
+                msg: "Running Docker services".to_string(),
             },
         )
     }
@@ -29,7 +29,7 @@ pub mod compose {
             run: format!("docker-compose up -d {}", PG_SERVICE_ID),
             env: EnvData::empty(),
             dir: Dir::Root,
-            msg: "Running detached Postgres service",
+            msg: "Running detached Postgres service".to_string(),
         }
     }
 
@@ -38,10 +38,15 @@ pub mod compose {
             run: format!("docker-compose stop {}", PG_SERVICE_ID),
             env: EnvData::empty(),
             dir: Dir::Root,
-            msg: "Stopping Postgres service",
+            msg: "Stopping Postgres service".to_string(),
         }
     }
 
+    // Runs a command inside of the Postgres container without allocating a TTY, so it can be piped
+    pub fn pg_exec(cmd: &str) -> String {
+        format!("docker-compose exec -T {} {}", PG_SERVICE_ID, cmd)
+    }
+
     pub async fn pg_status() -> Result<ServiceStatus, io::Error> {
         let cmd = "docker-compose ps --services --filter status=running";
 
@@ -1,15 +1,17 @@
-use crate::{services::docker, Cmd, Dir, Env, EnvData, Exec, Result, TcpAddr, CFG};
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use chrono::{Local, Utc};
+
+use crate::{error, services::docker, Cmd, Dir, Env, EnvData, Error, Exec, Result, CFG};
+
+const EXTERNAL_PG_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub fn create_database(env: &Env) -> Cmd {
     Cmd {
         run: "sqlx database create".to_string(),
         env: EnvData::one("DATABASE_URL", CFG.pg_url(env)),
         dir: Dir::Root,
-        msg: match env {
-            Env::Dev => "Creating development database",
-            Env::Prod => "Creating production database",
-            Env::Test => "Creating test database",
-        },
+        msg: format!("Creating {} database", env),
     }
 }
 
@@ -18,11 +20,7 @@ pub fn drop_database(env: &Env) -> Cmd {
         run: "sqlx database drop -y".to_string(),
         env: EnvData::one("DATABASE_URL", CFG.pg_url(env)),
         dir: Dir::Root,
-        msg: match env {
-            Env::Dev => "Dropping development database",
-            Env::Prod => "Dropping production database",
-            Env::Test => "Dropping test database",
-        },
+        msg: format!("Dropping {} database", env),
     }
 }
 
@@ -31,53 +29,231 @@ pub fn prepare_database_schema(env: &Env) -> Cmd {
         run: "cargo sqlx prepare".to_string(),
         env: EnvData::one("DATABASE_URL", CFG.pg_url(env)),
         dir: Dir::Api,
-        msg: match env {
-            Env::Dev => "Preparing schema against development database",
-            Env::Prod => "Preparing schema against production database",
-            Env::Test => "Preparing schema against test database",
-        },
+        msg: format!("Preparing schema against {} database", env),
     }
 }
 
-pub fn create_migration(name: String) -> Cmd {
-    Cmd {
-        run: format!("sqlx migrate add {}", name),
-        env: EnvData::one("DATABASE_URL", CFG.pg_url(&Env::Dev)),
-        dir: Dir::Api,
-        msg: "Creating migration",
+// Creates an empty up/down pair, so every new migration is reversible
+pub fn create_migration(name: &str) -> io::Result<Vec<PathBuf>> {
+    let version = Utc::now().format("%Y%m%d%H%M%S");
+    let dir = Dir::Migrations.loc();
+    let files = vec![
+        dir.join(format!("{}_{}.up.sql", version, name)),
+        dir.join(format!("{}_{}.down.sql", version, name)),
+    ];
+    for file in &files {
+        fs::write(file, "")?;
     }
+    Ok(files)
 }
 
+// Migrations are applied by the API binary, so no sqlx-cli is required to run them
 pub fn run_migrations(env: &Env) -> Cmd {
     Cmd {
-        run: "sqlx migrate run".to_string(),
-        env: EnvData::one("DATABASE_URL", CFG.pg_url(env)),
-        dir: Dir::Api,
-        msg: match env {
-            Env::Dev => "Running migrations against development database",
-            Env::Prod => "Running migrations against production database",
-            Env::Test => "Running migrations against test database",
+        run: "cargo run --package=api --quiet -- migrations run".to_string(),
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: format!("Running migrations against {} database", env),
+    }
+}
+
+pub fn revert_migrations(env: &Env, to: Option<i64>) -> Cmd {
+    Cmd {
+        run: match to {
+            Some(to) => format!(
+                "cargo run --package=api --quiet -- migrations revert --to {}",
+                Cmd::quote(&to.to_string())
+            ),
+            None => "cargo run --package=api --quiet -- migrations revert".to_string(),
         },
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: format!("Reverting migrations of {} database", env),
+    }
+}
+
+pub fn migrations_status(env: &Env) -> Cmd {
+    Cmd {
+        run: "cargo run --package=api --quiet -- migrations status".to_string(),
+        env: CFG.env(env),
+        dir: Dir::Root,
+        msg: format!("Status of {} database migrations", env),
+    }
+}
+
+pub fn dump_file(env: &Env) -> io::Result<String> {
+    let dir = Dir::Backups.loc();
+    fs::create_dir_all(&dir)?;
+    Ok(dir
+        .join(format!(
+            "{db}-{timestamp}.dump",
+            db = CFG.pg_database(env),
+            timestamp = Local::now().format("%Y%m%d-%H%M%S"),
+        ))
+        .display()
+        .to_string())
+}
+
+// Databases of isolated stacks (see `rrd e2e`) don't belong to any env, so these are managed by name
+pub fn create_isolated_database(name: &str) -> Cmd {
+    let (run, env) = client_cmd(
+        &Env::test(),
+        &format!(
+            "createdb --username={user} {db}",
+            user = CFG.pg_user(&Env::test()),
+            db = name,
+        ),
+    );
+    Cmd {
+        run,
+        env,
+        dir: Dir::Root,
+        msg: "Creating isolated database".to_string(),
     }
 }
 
-pub async fn run_one_off_cmds_against_db(cmds: Vec<Cmd>) -> Result {
-    let pg_status = docker::compose::pg_status().await?;
+pub fn drop_isolated_database(name: &str) -> Cmd {
+    let (run, env) = client_cmd(
+        &Env::test(),
+        &format!(
+            "dropdb --if-exists --force --username={user} {db}",
+            user = CFG.pg_user(&Env::test()),
+            db = name,
+        ),
+    );
+    Cmd {
+        run,
+        env,
+        dir: Dir::Root,
+        msg: "Dropping isolated database".to_string(),
+    }
+}
+
+// Dump is written in the custom format, so it can be restored selectively with `pg_restore`
+pub fn dump_database(env: &Env, file: &str) -> Cmd {
+    let (dump, vars) = client_cmd(
+        env,
+        &format!(
+            "pg_dump --format=custom --username={user} {db}",
+            user = CFG.pg_user(env),
+            db = CFG.pg_database(env),
+        ),
+    );
+    Cmd {
+        run: format!(
+            "{dump} > {file} || (rm -f {file} && false)",
+            dump = dump,
+            file = Cmd::quote(file),
+        ),
+        env: vars,
+        dir: Dir::Root,
+        msg: format!("Dumping {} database", env),
+    }
+}
+
+// Same as `dump_database`, but skips the dump when the database doesn't exist yet,
+// e.g. before production is set up or after it was dropped
+pub fn dump_existing_database(env: &Env, file: &str) -> Cmd {
+    let db = CFG.pg_database(env);
+    let (exists, _) = client_cmd(
+        env,
+        &format!(
+            "psql --username={user} --dbname=postgres --tuples-only --no-align \
+             --command=\"SELECT 1 FROM pg_database WHERE datname = '{db}'\"",
+            user = CFG.pg_user(env),
+            db = db,
+        ),
+    );
+    let dump = dump_database(env, file);
+    Cmd {
+        run: format!(
+            "if [ \"$({exists})\" = 1 ]; then {dump}; else echo 'Database {db} does not exist, nothing to dump'; fi",
+            exists = exists,
+            dump = dump.run,
+            db = db,
+        ),
+        env: dump.env,
+        dir: Dir::Root,
+        msg: dump.msg,
+    }
+}
 
-    if let docker::compose::ServiceStatus::Stopped = pg_status {
-        let pg = TcpAddr {
-            host: CFG.pg_host(&Env::Dev),
-            port: CFG.pg_port(&Env::Dev),
-        };
-        Exec::cmd(docker::compose::start_detached_pg()).await?;
-        pg.wait().await?;
+pub fn restore_database(env: &Env, file: &str) -> Cmd {
+    let (restore, vars) = client_cmd(
+        env,
+        &format!(
+            "pg_restore --clean --if-exists --no-owner --username={user} --dbname={db}",
+            user = CFG.pg_user(env),
+            db = CFG.pg_database(env),
+        ),
+    );
+    Cmd {
+        run: format!(
+            "{restore} < {file}",
+            restore = restore,
+            file = Cmd::quote(file)
+        ),
+        env: vars,
+        dir: Dir::Root,
+        msg: format!("Restoring {} database", env),
     }
+}
+
+// Commands run against databases of the given envs. The shared Postgres is started if it's stopped
+// and stopped again afterwards, external servers are expected to be up and are only waited on.
+pub async fn run_one_off_cmds_against_db(envs: &[Env], cmds: Vec<Cmd>) -> Result {
+    let started = ensure_pg(envs).await?;
 
-    Exec::cmd_seq(cmds).await?;
+    let res = Exec::cmd_seq(cmds).await;
 
-    if let docker::compose::ServiceStatus::Stopped = pg_status {
+    if started {
         Exec::cmd(docker::compose::stop_pg()).await?;
     }
 
-    Ok(())
+    res
+}
+
+// Returns `true` when the shared Postgres was started, so the caller knows it should be stopped afterwards
+pub async fn ensure_pg(envs: &[Env]) -> std::result::Result<bool, Error> {
+    for env in envs {
+        CFG.check_pg(env)?;
+    }
+    for env in envs.iter().filter(|env| CFG.pg_external(env)) {
+        CFG.pg_addr(env)
+            .wait_timeout(EXTERNAL_PG_TIMEOUT)
+            .await
+            .map_err(|error| {
+                error::other(format!("Postgres of {} env is unavailable. {}", env, error))
+            })?;
+    }
+    if envs.iter().all(|env| CFG.pg_external(env)) {
+        return Ok(false);
+    }
+
+    match docker::compose::pg_status().await? {
+        docker::compose::ServiceStatus::Running => Ok(false),
+        docker::compose::ServiceStatus::Stopped => {
+            Exec::cmd(docker::compose::start_detached_pg()).await?;
+            CFG.shared_pg_addr().wait().await?;
+            Ok(true)
+        }
+    }
+}
+
+// Client tools run inside the Postgres container for the shared instance, so nothing has to be
+// installed locally. External servers are reached with the locally installed tools.
+fn client_cmd(env: &Env, cmd: &str) -> (String, EnvData) {
+    if CFG.pg_external(env) {
+        (
+            format!(
+                "{cmd} --host={host} --port={port}",
+                cmd = cmd,
+                host = CFG.pg_host(env),
+                port = CFG.pg_port(env)
+            ),
+            EnvData::one("PGPASSWORD", CFG.pg_password(env)),
+        )
+    } else {
+        (docker::compose::pg_exec(cmd), EnvData::empty())
+    }
 }
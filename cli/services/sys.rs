@@ -1,11 +1,11 @@
-use crate::{error, Cmd, Dir, EnvData, Error, Exec, File, Result};
+use crate::{error, Cmd, Dir, Env, EnvData, Error, Exec, File, Result};
 
 fn check_node() -> Cmd {
     Cmd {
         run: "node --version".to_string(),
         env: EnvData::parent(),
         dir: Dir::Root,
-        msg: "Checking Node",
+        msg: "Checking Node".to_string(),
     }
 }
 
@@ -14,7 +14,7 @@ fn check_yarn() -> Cmd {
         run: "yarn --version".to_string(),
         env: EnvData::parent(),
         dir: Dir::Root,
-        msg: "Checking Yarn",
+        msg: "Checking Yarn".to_string(),
     }
 }
 
@@ -23,7 +23,7 @@ fn check_cargo_watch() -> Cmd {
         run: "cargo watch --version".to_string(),
         env: EnvData::parent(),
         dir: Dir::Root,
-        msg: "Checking Cargo watch",
+        msg: "Checking Cargo watch".to_string(),
     }
 }
 
@@ -36,46 +36,20 @@ fn copy_env_file() -> Cmd {
         ),
         env: EnvData::parent(),
         dir: Dir::Env,
-        msg: "Copying base env file",
+        msg: "Copying base env file".to_string(),
     }
 }
 
-fn copy_dev_env_file() -> Cmd {
+fn copy_env_overrides_file(env: &Env) -> Cmd {
     Cmd {
         run: format!(
             "cp {} {}",
-            File::DevEnvExample.file_name(),
-            File::DevEnv.file_name()
+            File::EnvOverridesExample(env.clone()).file_name(),
+            File::EnvOverrides(env.clone()).file_name()
         ),
         env: EnvData::parent(),
         dir: Dir::Env,
-        msg: "Copying development env file",
-    }
-}
-
-fn copy_prod_env_file() -> Cmd {
-    Cmd {
-        run: format!(
-            "cp {} {}",
-            File::ProdEnvExample.file_name(),
-            File::ProdEnv.file_name()
-        ),
-        env: EnvData::parent(),
-        dir: Dir::Env,
-        msg: "Copying production env file",
-    }
-}
-
-fn copy_test_env_file() -> Cmd {
-    Cmd {
-        run: format!(
-            "cp {} {}",
-            File::TestEnvExample.file_name(),
-            File::TestEnv.file_name()
-        ),
-        env: EnvData::parent(),
-        dir: Dir::Env,
-        msg: "Copying test env file",
+        msg: format!("Copying {} env file", env),
     }
 }
 
@@ -99,14 +73,11 @@ pub async fn ensure_prerequisites() -> Result {
     if !File::Env.exists() {
         Exec::cmd(copy_env_file()).await?;
     }
-    if !File::DevEnv.exists() {
-        Exec::cmd(copy_dev_env_file()).await?;
-    }
-    if !File::ProdEnv.exists() {
-        Exec::cmd(copy_prod_env_file()).await?;
-    }
-    if !File::TestEnv.exists() {
-        Exec::cmd(copy_test_env_file()).await?;
+    // Every `env.<name>.example` gets its `.env.<name>`, incl. custom envs like `staging`
+    for env in Env::discover("env.", ".example") {
+        if !File::EnvOverrides(env.clone()).exists() {
+            Exec::cmd(copy_env_overrides_file(&env)).await?;
+        }
     }
 
     Ok(())
@@ -5,7 +5,7 @@ pub fn install() -> Cmd {
         run: "yarn install".to_string(),
         env: EnvData::empty(),
         dir: Dir::Root,
-        msg: "Installing Yarn dependencies",
+        msg: "Installing Yarn dependencies".to_string(),
     }
 }
 
@@ -14,7 +14,7 @@ pub fn remove_root_node_modules() -> Cmd {
         run: "rm -rf node_modules".to_string(),
         env: EnvData::empty(),
         dir: Dir::Root,
-        msg: "Removing root node_modules",
+        msg: "Removing root node_modules".to_string(),
     }
 }
 
@@ -23,6 +23,6 @@ pub fn remove_client_node_modules() -> Cmd {
         run: "rm -rf node_modules".to_string(),
         env: EnvData::empty(),
         dir: Dir::Client,
-        msg: "Removing client node_modules",
+        msg: "Removing client node_modules".to_string(),
     }
 }
@@ -0,0 +1,271 @@
+// Declared schema of env variables shared by the API, the web client and this CLI.
+// `rrd env check` validates every env against it, so a typo in `env/.env.staging`
+// is reported upfront instead of as a panic at boot.
+
+use std::net::IpAddr;
+
+use crate::EnvData;
+
+pub enum Kind {
+    Str,
+    Bool,
+    Port,
+    Int,
+    // Number that is at least the given value
+    Float(f64),
+    // Number greater than 0
+    Positive,
+    IpList,
+    // URL path, e.g. `/api`
+    Path,
+    Host,
+    OneOf(&'static [&'static str]),
+}
+
+pub enum Presence {
+    // Must be set to a non-empty value
+    Required,
+    // Must be set, but can be empty
+    Declared,
+    // Can be omitted
+    Optional,
+}
+
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub presence: Presence,
+    pub secret: bool,
+}
+
+const fn key(name: &'static str, kind: Kind, presence: Presence) -> Key {
+    Key {
+        name,
+        kind,
+        presence,
+        secret: false,
+    }
+}
+
+const fn secret(name: &'static str, presence: Presence) -> Key {
+    Key {
+        name,
+        kind: Kind::Str,
+        presence,
+        secret: true,
+    }
+}
+
+pub static KEYS: &[Key] = &[
+    key("WEB_HOST", Kind::Host, Presence::Required),
+    key("WEB_PORT", Kind::Port, Presence::Required),
+    key("API_HOST", Kind::Host, Presence::Required),
+    key("API_PORT", Kind::Port, Presence::Required),
+    key("API_TLS_CERT", Kind::Str, Presence::Optional),
+    key("API_TLS_KEY", Kind::Str, Presence::Optional),
+    key("API_TLS_PORT", Kind::Port, Presence::Required),
+    key("API_TLS_REDIRECT", Kind::Bool, Presence::Required),
+    key("API_GRAPHQL_PATH", Kind::Path, Presence::Required),
+    key("API_GRAPHQL_BATCH_LIMIT", Kind::Int, Presence::Required),
+    key(
+        "API_GRAPHQL_SUBSCRIPTION_PATH",
+        Kind::Path,
+        Presence::Optional,
+    ),
+    key(
+        "API_INTROSPECTION",
+        Kind::OneOf(&["public", "token", "disabled"]),
+        Presence::Required,
+    ),
+    secret("API_INTROSPECTION_TOKEN", Presence::Optional),
+    key("API_EXPLORER", Kind::Bool, Presence::Required),
+    key("API_EXPLORER_PATH", Kind::Path, Presence::Required),
+    key("API_EXPLORER_EXAMPLES", Kind::Str, Presence::Optional),
+    key("API_MIGRATE", Kind::Bool, Presence::Required),
+    key("API_HEALTH_PATH", Kind::Path, Presence::Required),
+    key("API_READINESS_PATH", Kind::Path, Presence::Required),
+    key("API_METRICS_PATH", Kind::Path, Presence::Required),
+    key("API_CORS_ORIGINS", Kind::Str, Presence::Declared),
+    key(
+        "API_CORS_SCHEME",
+        Kind::OneOf(&["http", "https"]),
+        Presence::Required,
+    ),
+    key("API_CORS_ALLOWED_HEADERS", Kind::Str, Presence::Declared),
+    key("API_CORS_CREDENTIALS", Kind::Bool, Presence::Required),
+    key("API_CORS_MAX_AGE", Kind::Int, Presence::Required),
+    key(
+        "API_RATE_LIMIT_STORE",
+        Kind::OneOf(&["memory", "postgres", "disabled"]),
+        Presence::Required,
+    ),
+    key(
+        "API_RATE_LIMIT_QUERY_BURST",
+        Kind::Float(1.0),
+        Presence::Required,
+    ),
+    key(
+        "API_RATE_LIMIT_QUERY_RATE",
+        Kind::Positive,
+        Presence::Required,
+    ),
+    key(
+        "API_RATE_LIMIT_MUTATION_BURST",
+        Kind::Float(1.0),
+        Presence::Required,
+    ),
+    key(
+        "API_RATE_LIMIT_MUTATION_RATE",
+        Kind::Positive,
+        Presence::Required,
+    ),
+    key("API_TRUSTED_PROXIES", Kind::IpList, Presence::Declared),
+    key("API_SHUTDOWN_GRACE", Kind::Int, Presence::Required),
+    key("API_SHUTDOWN_TIMEOUT", Kind::Int, Presence::Required),
+    key("API_OTLP_ENDPOINT", Kind::Str, Presence::Optional),
+    key("PG_EXTERNAL", Kind::Bool, Presence::Optional),
+    key("PG_PROTECTED", Kind::Bool, Presence::Optional),
+    key("PG_HOST", Kind::Host, Presence::Required),
+    key("PG_PORT", Kind::Port, Presence::Required),
+    key("PG_USER", Kind::Str, Presence::Required),
+    secret("PG_PASSWORD", Presence::Declared),
+    key("PG_DATABASE", Kind::Str, Presence::Required),
+];
+
+const MASK: &str = "********";
+
+pub struct Report {
+    pub errors: Vec<String>,
+    // Keys that aren't declared here. Not an error, since env files can set e.g. `RUST_LOG`.
+    pub unknown: Vec<String>,
+}
+
+pub fn check(data: &EnvData) -> Report {
+    let mut errors = vec![];
+    for key in KEYS {
+        let value = match data.get(key.name) {
+            Some(value) => value,
+            None => {
+                match key.presence {
+                    Presence::Required | Presence::Declared => {
+                        errors.push(format!("{} is not set", key.name))
+                    }
+                    Presence::Optional => (),
+                }
+                continue;
+            }
+        };
+        if value.is_empty() {
+            match key.presence {
+                Presence::Required => errors.push(format!("{} is empty", key.name)),
+                Presence::Declared | Presence::Optional => (),
+            }
+            continue;
+        }
+        if let Err(expected) = validate(&key.kind, value) {
+            // Secrets must not leak into CI logs via error messages
+            let shown = if key.secret { MASK } else { value.as_str() };
+            errors.push(format!(
+                "{} must be {}, got `{}`",
+                key.name, expected, shown
+            ));
+        }
+    }
+
+    let tls_cert = data.get("API_TLS_CERT").filter(|value| !value.is_empty());
+    let tls_key = data.get("API_TLS_KEY").filter(|value| !value.is_empty());
+    if tls_cert.is_some() != tls_key.is_some() {
+        errors.push("API_TLS_CERT and API_TLS_KEY must be set together".to_string());
+    }
+
+    let introspection = data.get("API_INTROSPECTION").map(String::as_str);
+    let introspection_token = data
+        .get("API_INTROSPECTION_TOKEN")
+        .filter(|value| !value.is_empty());
+    if introspection == Some("token") && introspection_token.is_none() {
+        errors.push(
+            "API_INTROSPECTION_TOKEN must be set when API_INTROSPECTION is token".to_string(),
+        );
+    }
+    if introspection == Some("disabled")
+        && data.get("API_EXPLORER").map(String::as_str) == Some("true")
+    {
+        errors.push(
+            "API_EXPLORER requires API_INTROSPECTION to be either public or token".to_string(),
+        );
+    }
+
+    let mut unknown: Vec<String> = data
+        .clone()
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|name| !KEYS.iter().any(|key| key.name == *name))
+        .collect();
+    unknown.sort();
+
+    Report { errors, unknown }
+}
+
+fn validate(kind: &Kind, value: &str) -> Result<(), String> {
+    let valid = match kind {
+        Kind::Str => true,
+        Kind::Bool => value.parse::<bool>().is_ok(),
+        Kind::Port => matches!(value.parse::<u16>(), Ok(port) if port > 0),
+        Kind::Int => value.parse::<usize>().is_ok(),
+        Kind::Float(min) => matches!(value.parse::<f64>(), Ok(x) if x.is_finite() && x >= *min),
+        Kind::Positive => matches!(value.parse::<f64>(), Ok(x) if x.is_finite() && x > 0.0),
+        Kind::IpList => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .all(|item| item.parse::<IpAddr>().is_ok()),
+        Kind::Path => value.starts_with('/'),
+        Kind::Host => {
+            value.parse::<IpAddr>().is_ok()
+                || (!value.is_empty()
+                    && value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+        }
+        Kind::OneOf(values) => values.contains(&value),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(expected(kind))
+    }
+}
+
+fn expected(kind: &Kind) -> String {
+    match kind {
+        Kind::Str => "a string".to_string(),
+        Kind::Bool => "either true or false".to_string(),
+        Kind::Port => "a port number".to_string(),
+        Kind::Int => "a non-negative integer".to_string(),
+        Kind::Float(min) => format!("a number of at least {}", min),
+        Kind::Positive => "a number greater than 0".to_string(),
+        Kind::IpList => "a comma separated list of IP addresses".to_string(),
+        Kind::Path => "a path starting with /".to_string(),
+        Kind::Host => "a host name or an IP address".to_string(),
+        Kind::OneOf(values) => format!("one of: {}", values.join(", ")),
+    }
+}
+
+// Undeclared keys are masked when their names look like they hold credentials
+pub fn is_secret(name: &str) -> bool {
+    match KEYS.iter().find(|key| key.name == name) {
+        Some(key) => key.secret,
+        None => ["PASSWORD", "SECRET", "TOKEN"]
+            .iter()
+            .any(|word| name.contains(word)),
+    }
+}
+
+// Empty values are shown as is, so it's visible when a secret isn't set
+pub fn mask(value: &str) -> String {
+    if value.is_empty() {
+        String::new()
+    } else {
+        MASK.to_string()
+    }
+}
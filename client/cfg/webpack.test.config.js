@@ -0,0 +1,7 @@
+const development = require("./webpack.development.config.js");
+
+// Same as development, but without eval, so e2e stack traces point at real source lines
+module.exports = {
+  ...development,
+  devtool: "#cheap-module-source-map",
+};
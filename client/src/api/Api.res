@@ -109,6 +109,7 @@ let exec:
 
     xhr->openAsync("POST", `http://${Env.apiHost}:${Env.apiPort}${Env.apiPath}`)
     xhr->setRequestHeader("Content-Type", "application/json;charset=UTF-8")
+    xhr->setRequestHeader("traceparent", TraceParent.make())
 
     xhr->on(
       #readystatechange(
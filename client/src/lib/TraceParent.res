@@ -0,0 +1,9 @@
+// W3C Trace Context header: https://www.w3.org/TR/trace-context/#traceparent-header
+// Each request starts a new trace, so the API can link its spans to the originating request.
+
+let hex = length =>
+  Array.make(length, ())
+  ->Array.map(() => Js.Math.random_int(0, 16)->Js.Int.toStringWithRadix(~radix=16))
+  ->Js.Array2.joinWith("")
+
+let make = () => `00-${hex(32)}-${hex(16)}-01`